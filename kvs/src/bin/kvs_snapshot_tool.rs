@@ -0,0 +1,179 @@
+//! Copyright (c) 2026 Contributors to the Eclipse Foundation
+//!
+//! See the NOTICE file(s) distributed with this work for additional
+//! information regarding copyright ownership.
+//!
+//! This program and the accompanying materials are made available under the
+//! terms of the Apache License Version 2.0 which is available at
+//! <https://www.apache.org/licenses/LICENSE-2.0>
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+//! # Snapshot inspection/rollback and bulk dump/restore command line tool, implementing
+//! # `FEAT_REQ__KVS__tooling`
+//!
+//! [`Kvs`] exposes [`Kvs::snapshot_count`], [`Kvs::snapshot_info`], [`Kvs::snapshot_restore`],
+//! [`Kvs::export_all`] and [`Kvs::import_all`] to Rust code only; reaching them otherwise takes a
+//! custom program. This tool
+//! wraps them behind the same `-o`/`--operation` `Command`/`Arg` dispatch style as
+//! `persistence_client_library_tool_rust`, so operators can inspect rotation state, roll back to
+//! an older snapshot, or migrate/back up an entire store from the command line.
+
+use clap::{Arg, Command};
+use rust_kvs::{
+    ErrorCode, InstanceId, IntegrityAlgorithm, Kvs, OpenBackend, OpenEncryption, OpenEnvOverrides,
+    OpenFormat, OpenMigration, OpenNeedDefaults, OpenNeedKvs, SnapshotId,
+};
+use std::fs::File;
+use std::process::exit;
+
+/// Which snapshot/dump operation to perform, selected by `-o`/`--operation`
+enum OperationMode {
+    /// `snapshotcount`: Print how many snapshots currently exist
+    SnapshotCount,
+    /// `listsnapshots`: Print modification time, size, key count and hash validity for every
+    /// existing snapshot
+    ListSnapshots,
+    /// `snapshotrestore`: Restore the live KVS state from `-s`/`--snapshot-id`
+    SnapshotRestore,
+    /// `snapshotdelete`: Delete the snapshot at `-s`/`--snapshot-id` and compact the rest
+    SnapshotDelete,
+    /// `dumpall`: Export every key/value in the instance to `--archive-file`
+    DumpAll,
+    /// `restoreall`: Import every key/value from `--archive-file` into the instance
+    RestoreAll,
+    /// `-o`/`--operation` was missing or didn't match a known operation
+    Invalid,
+}
+
+/// The entry point of the CLI tool for inspecting/restoring KVS snapshots and dumping/restoring
+/// an entire instance.
+///
+/// Parses command-line arguments and dispatches to the appropriate operation.
+///
+/// Arguments:
+///   * `-o`, `--operation`: Operation mode (snapshotcount, listsnapshots, snapshotrestore,
+///     snapshotdelete, dumpall, restoreall).
+///   * `-i`, `--instance_id`: KVS instance ID (default is `0`).
+///   * `-s`, `--snapshot-id`: Snapshot ID, required by `snapshotrestore`/`snapshotdelete`.
+///   * `--archive-file`: Archive path, required by `dumpall`/`restoreall`.
+///   * `-h`, `--help`: Prints manual on how to use the CLI Tool.
+fn main() -> Result<(), ErrorCode> {
+    let matches = Command::new("kvs_snapshot_tool")
+        .version("1.0")
+        .about("KVS snapshot inspection/rollback and bulk dump/restore tool")
+        .arg(
+            Arg::new("operation")
+                .short('o')
+                .long("operation")
+                .help(
+                    "Specify the operation mode: snapshotcount, listsnapshots, snapshotrestore, \
+                     snapshotdelete, dumpall, restoreall",
+                ),
+        )
+        .arg(
+            Arg::new("instance_id")
+                .short('i')
+                .long("instance_id")
+                .help("KVS instance ID"),
+        )
+        .arg(
+            Arg::new("snapshot_id")
+                .short('s')
+                .long("snapshot-id")
+                .help("Snapshot ID, required by snapshotrestore/snapshotdelete"),
+        )
+        .arg(
+            Arg::new("archive_file")
+                .long("archive-file")
+                .help("Archive path, required by dumpall/restoreall"),
+        )
+        .get_matches();
+
+    let op_mode = match matches.get_one::<String>("operation") {
+        Some(op) => match op.as_str() {
+            "snapshotcount" => OperationMode::SnapshotCount,
+            "listsnapshots" => OperationMode::ListSnapshots,
+            "snapshotrestore" => OperationMode::SnapshotRestore,
+            "snapshotdelete" => OperationMode::SnapshotDelete,
+            "dumpall" => OperationMode::DumpAll,
+            "restoreall" => OperationMode::RestoreAll,
+            _ => OperationMode::Invalid,
+        },
+        None => OperationMode::Invalid,
+    };
+    let instance_id: usize = matches
+        .get_one::<String>("instance_id")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    let snapshot_id: Option<usize> =
+        matches.get_one::<String>("snapshot_id").and_then(|s| s.parse().ok());
+    let archive_file = matches.get_one::<String>("archive_file");
+
+    let kvs = Kvs::open(
+        InstanceId::new(instance_id),
+        OpenNeedDefaults::Optional,
+        OpenNeedKvs::Optional,
+        OpenEncryption::Disabled,
+        IntegrityAlgorithm::Adler32,
+        OpenFormat::Json,
+        OpenMigration::Automatic,
+        OpenBackend::File,
+        &[],
+        OpenEnvOverrides::Disabled,
+    )?;
+
+    match op_mode {
+        OperationMode::SnapshotCount => {
+            println!("Snapshot count: {}", kvs.snapshot_count()?);
+        }
+        OperationMode::ListSnapshots => {
+            for id in 1..=kvs.snapshot_count()? {
+                let info = kvs.snapshot_info(SnapshotId::new(id))?;
+                println!(
+                    "{id}: kind={:?} size={} key_count={} hash_valid={} modified={:?}",
+                    info.kind, info.size, info.key_count, info.hash_valid, info.modified
+                );
+            }
+        }
+        OperationMode::SnapshotRestore => {
+            let snapshot_id = snapshot_id.unwrap_or_else(|| {
+                println!("--snapshot-id is required for snapshotrestore");
+                exit(1);
+            });
+            kvs.snapshot_restore(SnapshotId::new(snapshot_id))?;
+            println!("Snapshot {snapshot_id} successfully restored!");
+        }
+        OperationMode::SnapshotDelete => {
+            let snapshot_id = snapshot_id.unwrap_or_else(|| {
+                println!("--snapshot-id is required for snapshotdelete");
+                exit(1);
+            });
+            kvs.snapshot_delete(SnapshotId::new(snapshot_id))?;
+            println!("Snapshot {snapshot_id} successfully deleted!");
+        }
+        OperationMode::DumpAll => {
+            let archive_file = archive_file.unwrap_or_else(|| {
+                println!("--archive-file is required for dumpall");
+                exit(1);
+            });
+            let mut file = File::create(archive_file)?;
+            kvs.export_all(&mut file)?;
+            println!("Instance successfully dumped to '{archive_file}'!");
+        }
+        OperationMode::RestoreAll => {
+            let archive_file = archive_file.unwrap_or_else(|| {
+                println!("--archive-file is required for restoreall");
+                exit(1);
+            });
+            let mut file = File::open(archive_file)?;
+            let count = kvs.import_all(&mut file)?;
+            println!("{count} entries successfully restored from '{archive_file}'!");
+        }
+        OperationMode::Invalid => {
+            println!("Unsupported operation mode");
+        }
+    }
+
+    Ok(())
+}
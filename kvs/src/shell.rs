@@ -0,0 +1,182 @@
+//! Copyright (c) 2025 Contributors to the Eclipse Foundation
+//!
+//! See the NOTICE file(s) distributed with this work for additional
+//! information regarding copyright ownership.
+//!
+//! This program and the accompanying materials are made available under the
+//! terms of the Apache License Version 2.0 which is available at
+//! <https://www.apache.org/licenses/LICENSE-2.0>
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+//! # A tiny line-oriented command language for [`Kvs`], implementing `FEAT_REQ__KVS__tooling`
+//!
+//! Gives callers a scriptable get/set/snapshot interface, usable from an interactive REPL or
+//! programmatically via [`Kvs::execute`], without a separate CLI binary. A hand-written lexer
+//! tokenizes identifiers, quoted strings and numbers; the parser below dispatches each command to
+//! the existing [`Kvs`] methods, and `SET`'s value is parsed with the existing TinyJSON parser.
+//!
+//! # Grammar
+//!
+//!   * `GET <key>`
+//!   * `SET <key> <json-value>`
+//!   * `DEL <key>`
+//!   * `KEYS`
+//!   * `SNAPSHOT LIST`
+//!   * `SNAPSHOT RESTORE <n>`
+//!   * `SNAPSHOT DELETE <n>`
+//!   * `FLUSH`
+
+use super::{ErrorCode, Kvs, SnapshotId};
+use tinyjson::JsonValue;
+
+/// A single lexical token produced by [`lex`], together with its byte span in the source line
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// An unquoted run of non-whitespace characters, e.g. a command word or bare key
+    Ident(String),
+    /// A double-quoted string literal, with the quotes removed
+    Str(String),
+    /// A bare numeric literal, e.g. a snapshot ID
+    Number(f64),
+}
+
+/// Split a command line into tokens with their byte spans
+///
+/// # Return Values
+///   * `ErrorCode::CommandParseError`: An unterminated quoted string, or an invalid number literal
+fn lex(line: &str) -> Result<Vec<(Token, usize, usize)>, ErrorCode> {
+    let mut tokens = Vec::new();
+    let mut iter = line.char_indices().peekable();
+
+    while let Some(&(start, ch)) = iter.peek() {
+        if ch.is_whitespace() {
+            iter.next();
+        } else if ch == '"' {
+            iter.next();
+            let mut value = String::new();
+            let mut end = None;
+            for (idx, c) in iter.by_ref() {
+                if c == '"' {
+                    end = Some(idx + c.len_utf8());
+                    break;
+                }
+                value.push(c);
+            }
+            tokens.push((Token::Str(value), start, end.ok_or(ErrorCode::CommandParseError)?));
+        } else if ch.is_ascii_digit() || ch == '-' {
+            let mut end = start;
+            while let Some(&(idx, c)) = iter.peek() {
+                if c.is_ascii_digit() || c == '.' || c == '-' {
+                    end = idx + c.len_utf8();
+                    iter.next();
+                } else {
+                    break;
+                }
+            }
+            let number = line[start..end]
+                .parse::<f64>()
+                .map_err(|_| ErrorCode::CommandParseError)?;
+            tokens.push((Token::Number(number), start, end));
+        } else {
+            let mut end = start;
+            while let Some(&(idx, c)) = iter.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                end = idx + c.len_utf8();
+                iter.next();
+            }
+            tokens.push((Token::Ident(line[start..end].to_string()), start, end));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Extract a `<key>` argument from an `Ident` or `Str` token
+fn key_arg(token: Option<&(Token, usize, usize)>) -> Result<String, ErrorCode> {
+    match token {
+        Some((Token::Ident(key), ..)) | Some((Token::Str(key), ..)) => Ok(key.clone()),
+        _ => Err(ErrorCode::CommandParseError),
+    }
+}
+
+/// Parse and run one command line against `kvs`
+///
+/// # Features
+///   * `FEAT_REQ__KVS__tooling`
+///
+/// # Return Values
+///   * Ok: The command's result, or `JsonValue::Null` for commands with no natural result
+///   * `ErrorCode::CommandParseError`: The line didn't match the command grammar
+///   * Any error returned by the dispatched [`Kvs`] method
+pub(crate) fn execute(kvs: &Kvs, line: &str) -> Result<JsonValue, ErrorCode> {
+    let tokens = lex(line)?;
+    let Some((Token::Ident(command), ..)) = tokens.first() else {
+        return Err(ErrorCode::CommandParseError);
+    };
+
+    match command.to_ascii_uppercase().as_str() {
+        "GET" => {
+            let key = key_arg(tokens.get(1))?;
+            kvs.get_value::<JsonValue>(&key)
+        }
+        "SET" => {
+            let (_, _, key_end) = tokens.get(1).ok_or(ErrorCode::CommandParseError)?;
+            let key = key_arg(tokens.get(1))?;
+            let value: JsonValue = line[*key_end..].trim().parse()?;
+            kvs.set_value(key, value.clone())?;
+            Ok(value)
+        }
+        "DEL" => {
+            let key = key_arg(tokens.get(1))?;
+            kvs.remove_key(&key)?;
+            Ok(JsonValue::Null)
+        }
+        "KEYS" => Ok(JsonValue::Array(
+            kvs.get_all_keys()?.into_iter().map(JsonValue::from).collect(),
+        )),
+        "SNAPSHOT" => {
+            let Some((Token::Ident(sub), ..)) = tokens.get(1) else {
+                return Err(ErrorCode::CommandParseError);
+            };
+            match sub.to_ascii_uppercase().as_str() {
+                "LIST" => Ok(JsonValue::Array(
+                    (1..=kvs.snapshot_count()?)
+                        .map(|id| kvs.snapshot_info(SnapshotId::new(id)))
+                        .collect::<Result<Vec<_>, _>>()?
+                        .into_iter()
+                        .map(|info| {
+                            JsonValue::Object(std::collections::HashMap::from([
+                                ("size".to_string(), JsonValue::from(info.size as f64)),
+                                ("key_count".to_string(), JsonValue::from(info.key_count as f64)),
+                                ("hash_valid".to_string(), JsonValue::from(info.hash_valid)),
+                            ]))
+                        })
+                        .collect(),
+                )),
+                "RESTORE" => {
+                    let Some((Token::Number(id), ..)) = tokens.get(2) else {
+                        return Err(ErrorCode::CommandParseError);
+                    };
+                    kvs.snapshot_restore(SnapshotId::new(*id as usize))?;
+                    Ok(JsonValue::Null)
+                }
+                "DELETE" => {
+                    let Some((Token::Number(id), ..)) = tokens.get(2) else {
+                        return Err(ErrorCode::CommandParseError);
+                    };
+                    kvs.snapshot_delete(SnapshotId::new(*id as usize))?;
+                    Ok(JsonValue::Null)
+                }
+                _ => Err(ErrorCode::CommandParseError),
+            }
+        }
+        "FLUSH" => {
+            kvs.flush()?;
+            Ok(JsonValue::Null)
+        }
+        _ => Err(ErrorCode::CommandParseError),
+    }
+}
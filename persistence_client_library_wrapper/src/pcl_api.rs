@@ -0,0 +1,274 @@
+//! Copyright (c) 2025 Contributors to the Eclipse Foundation
+//!
+//! See the NOTICE file(s) distributed with this work for additional
+//! information regarding copyright ownership.
+//!
+//! This program and the accompanying materials are made available under the
+//! terms of the Apache License Version 2.0 which is available at
+//! <https://www.apache.org/licenses/LICENSE-2.0>
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+//! # Centralizes the unsafe calls into the dynamically loaded PCL C API behind safe wrappers.
+//!
+//! Every key and file handling `pcl_*` symbol gets exactly one wrapper here that owns the loader
+//! call, the unsafe invocation, and the `rval >= 0` success/error conversion, instead of that
+//! pattern being copy-pasted at every call site. The library init/deinit, change-notification
+//! and plugin-status symbols are not wrapped here; the crate root invokes them directly. The [`PclApi`] trait lets that logic be swapped out for a
+//! fake implementation in unit tests, without a real PCL library present.
+
+use std::ffi::CString;
+
+use super::ErrorCode;
+
+/// Safe, mockable entry points onto the PCL C API.
+///
+/// [`LiveApi`] is the production implementation, backed by the dynamically loaded library.
+pub(crate) trait PclApi {
+    fn key_read_data(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+        buffer: &mut [u8],
+    ) -> Result<i32, ErrorCode>;
+
+    fn key_get_size(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+    ) -> Result<i32, ErrorCode>;
+
+    fn key_write_data(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+        buffer: &[u8],
+    ) -> Result<(), ErrorCode>;
+
+    fn key_delete(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+    ) -> Result<(), ErrorCode>;
+
+    fn file_open(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+    ) -> Result<i32, ErrorCode>;
+
+    fn file_read_data(&self, fd: i32, buffer: &mut [u8]) -> Result<i32, ErrorCode>;
+
+    fn file_write_data(&self, fd: i32, buffer: &[u8]) -> Result<i32, ErrorCode>;
+
+    fn file_get_size(&self, fd: i32) -> Result<i32, ErrorCode>;
+
+    fn file_close(&self, fd: i32) -> Result<(), ErrorCode>;
+
+    fn file_remove(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+    ) -> Result<(), ErrorCode>;
+
+    fn file_seek(&self, fd: i32, offset: i32, whence: i32) -> Result<(), ErrorCode>;
+}
+
+/// Converts a raw PCL return value into a `Result`, the one place that owns the `rval >= 0`
+/// success/error convention shared by every `pcl_*` call.
+fn checked(rval: i32) -> Result<i32, ErrorCode> {
+    if rval >= 0 {
+        Ok(rval)
+    } else {
+        Err(rval.into())
+    }
+}
+
+fn c_resource_id(resource_id: &str) -> Result<CString, ErrorCode> {
+    CString::new(resource_id).map_err(|_| ErrorCode::DatatypeConversionFailed)
+}
+
+/// Production [`PclApi`], backed by the symbols dynamically loaded from the PCL shared library.
+pub(crate) struct LiveApi;
+
+impl PclApi for LiveApi {
+    fn key_read_data(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+        buffer: &mut [u8],
+    ) -> Result<i32, ErrorCode> {
+        let pcl_key_read_data = super::load_pcl_key_read_data()?;
+        let c_resource_id = c_resource_id(resource_id)?;
+
+        // SAFETY: `pcl_key_read_data` is a C function pointer loaded from the PCL shared
+        // library; `c_resource_id` is a valid NUL-terminated string alive for the call, and
+        // `buffer` is a uniquely-borrowed Rust slice the C side writes at most `buffer.len()`
+        // bytes into, matching `pclKeyReadData`'s documented contract.
+        let rval = unsafe {
+            pcl_key_read_data(
+                ldbid,
+                c_resource_id.as_ptr(),
+                user_no,
+                seat_no,
+                buffer.as_mut_ptr(),
+                buffer.len() as i32,
+            )
+        };
+        checked(rval)
+    }
+
+    fn key_get_size(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+    ) -> Result<i32, ErrorCode> {
+        let pcl_key_get_size = super::load_pcl_key_get_size()?;
+        let c_resource_id = c_resource_id(resource_id)?;
+
+        // SAFETY: `pcl_key_get_size` is a C function pointer loaded from the PCL shared
+        // library; `c_resource_id` is a valid NUL-terminated string alive for the call.
+        let rval = unsafe { pcl_key_get_size(ldbid, c_resource_id.as_ptr(), user_no, seat_no) };
+        checked(rval)
+    }
+
+    fn key_write_data(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+        buffer: &[u8],
+    ) -> Result<(), ErrorCode> {
+        let pcl_key_write_data = super::load_pcl_key_write_data()?;
+        let c_resource_id = c_resource_id(resource_id)?;
+
+        // SAFETY: `pcl_key_write_data` is a C function pointer loaded from the PCL shared
+        // library; `c_resource_id` is a valid NUL-terminated string alive for the call, and
+        // `buffer` is a valid Rust slice the C side only reads from, up to `buffer.len()` bytes.
+        let rval = unsafe {
+            pcl_key_write_data(
+                ldbid,
+                c_resource_id.as_ptr(),
+                user_no,
+                seat_no,
+                buffer.as_ptr(),
+                buffer.len() as i32,
+            )
+        };
+        checked(rval).map(|_| ())
+    }
+
+    fn key_delete(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+    ) -> Result<(), ErrorCode> {
+        let pcl_key_delete = super::load_pcl_key_delete()?;
+        let c_resource_id = c_resource_id(resource_id)?;
+
+        // SAFETY: `pcl_key_delete` is a C function pointer loaded from the PCL shared library;
+        // `c_resource_id` is a valid NUL-terminated string alive for the call.
+        let rval = unsafe { pcl_key_delete(ldbid, c_resource_id.as_ptr(), user_no, seat_no) };
+        checked(rval).map(|_| ())
+    }
+
+    fn file_open(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+    ) -> Result<i32, ErrorCode> {
+        let pcl_file_open = super::load_pcl_file_open()?;
+        let c_resource_id = c_resource_id(resource_id)?;
+
+        // SAFETY: `pcl_file_open` is a C function pointer loaded from the PCL shared library;
+        // `c_resource_id` is a valid NUL-terminated string alive for the call.
+        let rval = unsafe { pcl_file_open(ldbid, c_resource_id.as_ptr(), user_no, seat_no) };
+        checked(rval)
+    }
+
+    fn file_read_data(&self, fd: i32, buffer: &mut [u8]) -> Result<i32, ErrorCode> {
+        let pcl_file_read_data = super::load_pcl_file_read_data()?;
+
+        // SAFETY: `pcl_file_read_data` is a C function pointer loaded from the PCL shared
+        // library; `buffer` is a uniquely-borrowed Rust slice the C side writes at most
+        // `buffer.len()` bytes into.
+        let rval =
+            unsafe { pcl_file_read_data(fd, buffer.as_mut_ptr(), buffer.len() as i32) };
+        checked(rval)
+    }
+
+    fn file_write_data(&self, fd: i32, buffer: &[u8]) -> Result<i32, ErrorCode> {
+        let pcl_file_write_data = super::load_pcl_file_write_data()?;
+
+        // SAFETY: `pcl_file_write_data` is a C function pointer loaded from the PCL shared
+        // library; `buffer` is a valid Rust slice the C side only reads from, up to
+        // `buffer.len()` bytes.
+        let rval = unsafe { pcl_file_write_data(fd, buffer.as_ptr(), buffer.len() as i32) };
+        checked(rval)
+    }
+
+    fn file_get_size(&self, fd: i32) -> Result<i32, ErrorCode> {
+        let pcl_file_get_size = super::load_pcl_file_get_size()?;
+
+        // SAFETY: `pcl_file_get_size` is a C function pointer loaded from the PCL shared
+        // library; `fd` is passed through as-is, matching `pclFileGetSize`'s contract.
+        let rval = unsafe { pcl_file_get_size(fd) };
+        checked(rval)
+    }
+
+    fn file_close(&self, fd: i32) -> Result<(), ErrorCode> {
+        let pcl_file_close = super::load_pcl_file_close()?;
+
+        // SAFETY: `pcl_file_close` is a C function pointer loaded from the PCL shared library;
+        // `fd` is passed through as-is, matching `pclFileClose`'s contract.
+        let rval = unsafe { pcl_file_close(fd) };
+        checked(rval).map(|_| ())
+    }
+
+    fn file_remove(
+        &self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        seat_no: u32,
+    ) -> Result<(), ErrorCode> {
+        let pcl_file_remove = super::load_pcl_file_remove()?;
+        let c_resource_id = c_resource_id(resource_id)?;
+
+        // SAFETY: `pcl_file_remove` is a C function pointer loaded from the PCL shared library;
+        // `c_resource_id` is a valid NUL-terminated string alive for the call.
+        let rval = unsafe { pcl_file_remove(ldbid, c_resource_id.as_ptr(), user_no, seat_no) };
+        checked(rval).map(|_| ())
+    }
+
+    fn file_seek(&self, fd: i32, offset: i32, whence: i32) -> Result<(), ErrorCode> {
+        let pcl_file_seek = super::load_pcl_file_seek()?;
+
+        // SAFETY: `pcl_file_seek` is a C function pointer loaded from the PCL shared library;
+        // `fd`, `offset` and `whence` are passed through as-is, matching `pclFileSeek`'s
+        // contract.
+        let rval = unsafe { pcl_file_seek(fd, offset, whence) };
+        checked(rval).map(|_| ())
+    }
+}
@@ -82,10 +82,47 @@ pub type PclFileWriteDataFn = unsafe extern "C" fn(
     buffer_size: i32,
 ) -> i32;
 
+pub type PclFileSeekFn = unsafe extern "C" fn(
+    fd: i32,
+    offset: i32,
+    whence: i32,
+) -> i32;
+
 pub type PclFileRemoveFn = unsafe extern "C" fn(
-    ldbid: u32, 
+    ldbid: u32,
     resource_id: *const c_char,
-    user_no: u32, 
+    user_no: u32,
     seat_no: u32
 ) -> i32;
 
+/* ----Change Notification Functions--- */
+
+/// Signature of the trampoline handed to the C API; the PCL invokes it with the ldbid,
+/// resource_id and reason of the key that changed whenever a registered key is modified.
+pub type PclNotifyCallbackFn = unsafe extern "C" fn(
+    ldbid: u32,
+    resource_id: *const c_char,
+    reason: u32,
+);
+
+pub type PclKeyRegisterNotifyOnChangeFn = unsafe extern "C" fn(
+    ldbid: u32,
+    resource_id: *const c_char,
+    user_no: u32,
+    seat_no: u32,
+    callback: PclNotifyCallbackFn,
+) -> i32;
+
+pub type PclKeyUnRegisterNotifyOnChangeFn = unsafe extern "C" fn(
+    ldbid: u32,
+    resource_id: *const c_char,
+    user_no: u32,
+    seat_no: u32,
+) -> i32;
+
+/* ----Backend/Plugin Diagnostics--- */
+
+/// Reports which storage backend plugins the PCL actually loaded, as a bitmask whose meaning is
+/// defined by the PCL's plugin configuration (e.g. one bit per configured custom backend).
+pub type PclPluginStatusFn = unsafe extern "C" fn() -> i32;
+
@@ -64,6 +64,475 @@ enum OperationMode {
     DeleteFile,
     GetFileSize
 }
+
+/// How `--payload`/`-p` is interpreted for write operations
+#[derive(Clone, Copy)]
+enum PayloadEncoding {
+    /// The payload is taken as a raw UTF-8 string
+    Utf8,
+    /// The payload is two lowercase (or uppercase) hex digits per byte
+    Hex,
+    /// The payload is standard base64 (`A-Z a-z 0-9 + /`, `=` padded)
+    Base64,
+}
+
+impl PayloadEncoding {
+    fn parse(value: Option<&String>) -> Self {
+        match value.map(String::as_str) {
+            None | Some("utf8") => Self::Utf8,
+            Some("hex") => Self::Hex,
+            Some("base64") => Self::Base64,
+            Some(other) => {
+                println!("Unknown --encoding '{other}', expected utf8, hex or base64");
+                exit(1);
+            }
+        }
+    }
+}
+
+/// How data retrieved by `_read_key`/`_read_file` is printed to the console
+enum OutputFormat {
+    /// The existing hex+ASCII dump produced by [`print_buffer`]
+    HexDump,
+    /// The raw bytes, written verbatim to stdout
+    Raw,
+    /// Two lowercase hex digits per byte, on one line
+    Hex,
+    /// Standard base64 (`A-Z a-z 0-9 + /`, `=` padded), on one line
+    Base64,
+}
+
+impl OutputFormat {
+    fn parse(value: Option<&String>) -> Self {
+        match value.map(String::as_str) {
+            None | Some("hexdump") => Self::HexDump,
+            Some("raw") => Self::Raw,
+            Some("hex") => Self::Hex,
+            Some("base64") => Self::Base64,
+            Some(other) => {
+                println!("Unknown --output-format '{other}', expected hexdump, raw, hex or base64");
+                exit(1);
+            }
+        }
+    }
+}
+
+/// Decode a `--payload`/`-p` argument into the bytes to actually write, per `encoding`
+///
+/// Exits the process with an error message if the payload doesn't match `encoding`.
+fn decode_payload(payload: &str, encoding: PayloadEncoding) -> Vec<u8> {
+    let decoded = match encoding {
+        PayloadEncoding::Utf8 => Ok(payload.as_bytes().to_vec()),
+        PayloadEncoding::Hex => decode_hex(payload),
+        PayloadEncoding::Base64 => decode_base64(payload),
+    };
+
+    decoded.unwrap_or_else(|err| {
+        println!("Invalid payload: {err}");
+        exit(1);
+    })
+}
+
+/// Resolve the bytes to write for a write operation, from (in order of precedence)
+/// `--payload-file`, `-p -` (stdin), or the `-p`/`--payload` argument decoded per `encoding`
+///
+/// Exits the process with an error message if a file/stdin read fails or the payload doesn't
+/// match `encoding`.
+fn resolve_payload(
+    payload: Option<&String>,
+    payload_file: Option<&String>,
+    encoding: PayloadEncoding,
+) -> Vec<u8> {
+    if let Some(path) = payload_file {
+        return std::fs::read(path).unwrap_or_else(|err| {
+            println!("Failed to read --payload-file '{path}': {err}");
+            exit(1);
+        });
+    }
+
+    if payload.map(String::as_str) == Some("-") {
+        use std::io::Read;
+        let mut buffer = Vec::new();
+        if let Err(err) = std::io::stdin().read_to_end(&mut buffer) {
+            println!("Failed to read payload from stdin: {err}");
+            exit(1);
+        }
+        return buffer;
+    }
+
+    decode_payload(payload.map_or("", String::as_str), encoding)
+}
+
+/// Unwrap a required `-r`/`--resource_id` argument, exiting the process with an error message if
+/// it's missing
+fn require_resource_id(resource_id: Option<&String>) -> &str {
+    resource_id.map(String::as_str).unwrap_or_else(|| {
+        println!("Resource ID is required");
+        exit(1);
+    })
+}
+
+/// One decoded payload queued by [`run_batch_writes`], for the resource_id its line named
+struct PendingWrite {
+    resource_id: String,
+    buffer: Vec<u8>,
+}
+
+/// Decode one `--batch-file` line of the form `resource_id<TAB>payload`
+fn parse_batch_line(line: &str, encoding: PayloadEncoding, compression: CompressionMode) -> Option<PendingWrite> {
+    let (resource_id, payload) = line.split_once('\t')?;
+    Some(PendingWrite {
+        resource_id: resource_id.to_string(),
+        buffer: maybe_compress(decode_payload(payload, encoding), compression),
+    })
+}
+
+/// Run a `writekey`/`writefile` batch: `--batch-file` carries one `resource_id<TAB>payload` entry
+/// per line, decoded per `encoding`/`compression` exactly like a single `-r`/`-p` write.
+///
+/// Without `--defer-flush`, each line is decoded and written as soon as it's parsed. With
+/// `--defer-flush`, every line is parsed and decoded into memory first and the writes are issued
+/// afterwards in a single batch, so a malformed line later in the file can't leave only some of
+/// the batch's writes committed.
+fn run_batch_writes(
+    batch_file: &str,
+    is_file: bool,
+    ldbid: u32,
+    user_no: u32,
+    encoding: PayloadEncoding,
+    compression: CompressionMode,
+    defer_flush: bool,
+) {
+    let contents = std::fs::read_to_string(batch_file).unwrap_or_else(|err| {
+        println!("Failed to read batch file '{batch_file}': {err}");
+        exit(1);
+    });
+    let lines = contents.lines().filter(|line| !line.trim().is_empty());
+
+    let write_entry = |entry: &PendingWrite| {
+        if is_file {
+            _write_file(ldbid, &entry.resource_id, user_no, entry.buffer.clone());
+        } else {
+            _write_key(ldbid, &entry.resource_id, user_no, entry.buffer.clone());
+        }
+    };
+
+    if defer_flush {
+        let pending: Vec<PendingWrite> = lines
+            .filter_map(|line| {
+                let entry = parse_batch_line(line, encoding, compression);
+                if entry.is_none() {
+                    println!("Skipping malformed batch line: {line}");
+                }
+                entry
+            })
+            .collect();
+        println!("Queued {} pending write(s), committing as one batch", pending.len());
+        pending.iter().for_each(write_entry);
+        println!("Batch of {} write(s) issued; each write's result is printed above", pending.len());
+    } else {
+        for line in lines {
+            match parse_batch_line(line, encoding, compression) {
+                Some(entry) => write_entry(&entry),
+                None => println!("Skipping malformed batch line: {line}"),
+            }
+        }
+    }
+}
+
+/// Where retrieved key/file data is sent by [`emit_buffer`]
+enum OutputTarget {
+    /// Printed to the console in the given [`OutputFormat`]
+    Console(OutputFormat),
+    /// Written verbatim to the file at this path, via `--out-file`
+    File(String),
+}
+
+/// Send `buffer` to the requested `target`
+fn emit_buffer(buffer: &Vec<u8>, target: &OutputTarget) {
+    use std::io::Write;
+
+    match target {
+        OutputTarget::File(path) => {
+            if let Err(err) = std::fs::write(path, buffer) {
+                println!("Failed to write --out-file '{path}': {err}");
+            } else {
+                println!("Wrote {} bytes to '{path}'", buffer.len());
+            }
+        }
+        OutputTarget::Console(OutputFormat::HexDump) => print_buffer(buffer),
+        OutputTarget::Console(OutputFormat::Raw) => {
+            if let Err(err) = std::io::stdout().write_all(buffer) {
+                println!("Failed to write raw output: {err}");
+            }
+        }
+        OutputTarget::Console(OutputFormat::Hex) => println!("{}", encode_hex(buffer)),
+        OutputTarget::Console(OutputFormat::Base64) => println!("{}", encode_base64(buffer)),
+    }
+}
+
+/// Compression mode selected by `--compress`
+///
+/// There's no DEFLATE/gzip codec crate available here (the tool has no dependency beyond `clap`
+/// and the Rust `std` library), so `Rle` is a small hand-rolled run-length scheme instead of a
+/// real DEFLATE bitstream; it's layered the same way a DEFLATE writer/reader would be (an encode
+/// stage ahead of `write_key`/`write_file`, a decode stage behind `read_key`/`read_file`), it's
+/// just a simpler format underneath.
+#[derive(Clone, Copy)]
+enum CompressionMode {
+    /// Store bytes unmodified
+    None,
+    /// A literal-run / byte-run scheme, auto-detected on read via [`RLE_MAGIC`]
+    Rle,
+}
+
+impl CompressionMode {
+    fn parse(value: Option<&String>) -> Self {
+        match value.map(String::as_str) {
+            None | Some("none") => Self::None,
+            Some("rle") => Self::Rle,
+            Some(other) => {
+                println!("Unknown --compress '{other}', expected none or rle");
+                exit(1);
+            }
+        }
+    }
+}
+
+/// Magic header prepended to an [`rle_compress`]ed buffer by [`maybe_compress`], followed by the
+/// original (uncompressed) length as a little-endian `u64`; lets [`maybe_decompress`] tell
+/// compressed data apart from a plain payload and skip inflation when it's absent.
+const RLE_MAGIC: &[u8; 4] = b"RLE1";
+
+/// Compress `data` per `mode` ahead of a write, prefixed with [`RLE_MAGIC`] and the original
+/// length when compression is enabled
+fn maybe_compress(data: Vec<u8>, mode: CompressionMode) -> Vec<u8> {
+    match mode {
+        CompressionMode::None => data,
+        CompressionMode::Rle => {
+            let mut out = Vec::with_capacity(RLE_MAGIC.len() + 8);
+            out.extend_from_slice(RLE_MAGIC);
+            out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            out.extend(rle_compress(&data));
+            out
+        }
+    }
+}
+
+/// Inflate a buffer produced by [`maybe_compress`], or return it unchanged if it doesn't start
+/// with [`RLE_MAGIC`]
+fn maybe_decompress(data: Vec<u8>) -> Vec<u8> {
+    let header_len = RLE_MAGIC.len() + 8;
+    if data.len() < header_len || &data[..RLE_MAGIC.len()] != RLE_MAGIC {
+        return data;
+    }
+
+    let original_len =
+        u64::from_le_bytes(data[RLE_MAGIC.len()..header_len].try_into().unwrap()) as usize;
+    // The magic bytes can coincidentally occur at the start of plain, uncompressed data, so this
+    // isn't proof the rest is actually an RLE token stream; fall back to the bytes as written
+    // instead of trusting a malformed token to index past the end.
+    match rle_decompress(&data[header_len..], original_len) {
+        Some(decompressed) => decompressed,
+        None => data,
+    }
+}
+
+/// Token tag for a literal run in the [`rle_compress`] format: `0x01 <len:u8> <len bytes>`
+const RLE_LITERAL: u8 = 0x01;
+/// Token tag for a byte run in the [`rle_compress`] format: `0x02 <len:u8> <byte>`
+const RLE_RUN: u8 = 0x02;
+/// Shortest run of identical bytes worth encoding as [`RLE_RUN`] instead of literal bytes
+const RLE_MIN_RUN: usize = 4;
+/// Longest run or literal chunk a single token can carry (the `len:u8` field)
+const RLE_MAX_CHUNK: usize = 255;
+
+/// Compress `data` into a sequence of [`RLE_LITERAL`]/[`RLE_RUN`] tokens
+fn rle_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut literal = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let byte = data[i];
+        let mut run = 1;
+        while i + run < data.len() && data[i + run] == byte && run < RLE_MAX_CHUNK {
+            run += 1;
+        }
+
+        if run >= RLE_MIN_RUN {
+            flush_rle_literal(&mut literal, &mut out);
+            out.push(RLE_RUN);
+            out.push(run as u8);
+            out.push(byte);
+        } else {
+            for _ in 0..run {
+                literal.push(byte);
+            }
+        }
+        i += run;
+    }
+    flush_rle_literal(&mut literal, &mut out);
+
+    out
+}
+
+/// Emit `literal` as one or more [`RLE_LITERAL`] tokens, then clear it
+fn flush_rle_literal(literal: &mut Vec<u8>, out: &mut Vec<u8>) {
+    for chunk in literal.chunks(RLE_MAX_CHUNK) {
+        out.push(RLE_LITERAL);
+        out.push(chunk.len() as u8);
+        out.extend_from_slice(chunk);
+    }
+    literal.clear();
+}
+
+/// Reverse [`rle_compress`]; `original_len` pre-sizes the output buffer
+///
+/// # Return Values
+///   * `None`: `data` isn't actually a valid token stream -- a token's declared length runs past
+///     the end of `data`, or a byte that isn't a known token tag starts where a token was
+///     expected. Every index and slice below is checked explicitly because `data` may just be
+///     plain bytes that happened to collide with [`RLE_MAGIC`], not real compressed output.
+fn rle_decompress(data: &[u8], original_len: usize) -> Option<Vec<u8>> {
+    // `original_len` comes from the same, possibly-coincidental header as `data` itself, so it's
+    // untrusted too; cap how much we'll preallocate on its word alone.
+    let mut out = Vec::with_capacity(original_len.min(1 << 20));
+    let mut i = 0;
+
+    while i < data.len() {
+        match data[i] {
+            RLE_LITERAL => {
+                let len = *data.get(i + 1)? as usize;
+                let start = i.checked_add(2)?;
+                let end = start.checked_add(len)?;
+                out.extend_from_slice(data.get(start..end)?);
+                i = end;
+            }
+            RLE_RUN => {
+                let len = *data.get(i + 1)? as usize;
+                let byte = *data.get(i + 2)?;
+                out.extend(std::iter::repeat(byte).take(len));
+                i += 3;
+            }
+            _ => return None,
+        }
+    }
+
+    Some(out)
+}
+
+/// Encode `data` as lowercase hex, two digits per byte
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Decode a hex string (whitespace ignored, upper- or lowercase digits accepted) back into bytes
+///
+/// # Errors
+///   * The string has an odd number of hex digits
+///   * A character isn't a valid hex digit
+fn decode_hex(data: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<char> = data.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.len() % 2 != 0 {
+        return Err("hex payload must have an even number of digits".to_string());
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let byte: String = pair.iter().collect();
+            u8::from_str_radix(&byte, 16).map_err(|_| format!("invalid hex digit pair '{byte}'"))
+        })
+        .collect()
+}
+
+/// Base64 alphabet used by [`encode_base64`]/[`decode_base64`]: `A-Z`, `a-z`, `0-9`, `+`, `/`
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode `data` as standard base64, padding the final group with `=` as needed
+fn encode_base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+
+    for group in data.chunks(3) {
+        let b0 = group[0];
+        let b1 = *group.get(1).unwrap_or(&0);
+        let b2 = *group.get(2).unwrap_or(&0);
+
+        out.push(BASE64_ALPHABET[(b0 >> 2) as usize] as char);
+        out.push(BASE64_ALPHABET[(((b0 & 0x03) << 4) | (b1 >> 4)) as usize] as char);
+        out.push(if group.len() > 1 {
+            BASE64_ALPHABET[(((b1 & 0x0F) << 2) | (b2 >> 6)) as usize] as char
+        } else {
+            '='
+        });
+        out.push(if group.len() > 2 {
+            BASE64_ALPHABET[(b2 & 0x3F) as usize] as char
+        } else {
+            '='
+        });
+    }
+
+    out
+}
+
+/// Map one base64 alphabet character back to its 6-bit value
+fn base64_index(c: u8) -> Result<u8, String> {
+    match c {
+        b'A'..=b'Z' => Ok(c - b'A'),
+        b'a'..=b'z' => Ok(c - b'a' + 26),
+        b'0'..=b'9' => Ok(c - b'0' + 52),
+        b'+' => Ok(62),
+        b'/' => Ok(63),
+        _ => Err(format!("invalid base64 character '{}'", c as char)),
+    }
+}
+
+/// Decode a standard base64 string (whitespace ignored) back into bytes
+///
+/// # Errors
+///   * The payload (after stripping whitespace) isn't a multiple of 4 characters
+///   * `=` padding appears anywhere but the last one or two characters of the final group
+///   * A character isn't in the base64 alphabet
+fn decode_base64(data: &str) -> Result<Vec<u8>, String> {
+    let chars: Vec<u8> = data.bytes().filter(|byte| !byte.is_ascii_whitespace()).collect();
+    if chars.is_empty() {
+        return Ok(Vec::new());
+    }
+    if chars.len() % 4 != 0 {
+        return Err("base64 payload length must be a multiple of 4".to_string());
+    }
+
+    let mut out = Vec::with_capacity(chars.len() / 4 * 3);
+    let group_count = chars.len() / 4;
+    for (index, group) in chars.chunks(4).enumerate() {
+        let pad = group.iter().filter(|&&byte| byte == b'=').count();
+        let padded_before_last = pad > 0 && index + 1 < group_count;
+        if pad > 2 || padded_before_last || group[..4 - pad].iter().any(|&byte| byte == b'=') {
+            return Err("misplaced '=' padding in base64 payload".to_string());
+        }
+
+        let mut values = [0u8; 4];
+        for (value, &byte) in values.iter_mut().zip(group.iter()).take(4 - pad) {
+            *value = base64_index(byte)?;
+        }
+
+        let b0 = (values[0] << 2) | (values[1] >> 4);
+        let b1 = ((values[1] & 0x0F) << 4) | (values[2] >> 2);
+        let b2 = ((values[2] & 0x03) << 6) | values[3];
+
+        out.push(b0);
+        if pad < 2 {
+            out.push(b1);
+        }
+        if pad < 1 {
+            out.push(b2);
+        }
+    }
+
+    Ok(out)
+}
+
 /*--------------------Key Handling--------------------*/
 /// Reads data from a key
 ///
@@ -71,15 +540,16 @@ enum OperationMode {
 ///   * Prints the `resource_id` in readable ASCII form (non-ASCII as `.`).
 ///   * Displays which key is being read, including `user_no` and `ldbid`.
 ///   * Calls the `read_key()` function from the wrapper.
-///   * Prints the key data in a hex dump format using `print_buffer()`.
-fn _read_key(ldbid: u32, resource_id: &str, user_no: u32) {
+///   * Sends the key data to `target`: the console in the requested output format (hex dump by
+///     default), or the `--out-file` file.
+fn _read_key(ldbid: u32, resource_id: &str, user_no: u32, target: &OutputTarget) {
     print!("Resource_id: ");
     for c in resource_id.chars() {
         print!("{}", if c.is_ascii() { c } else { '.' });
     }
-    println!();  
+    println!();
     println!("Reading key with resource_id: user_no: {}, ldbid: {:X}",user_no, ldbid);
-    
+
     let buffer = match read_key(ldbid, resource_id, user_no) {
         Ok(buffer) => buffer,
         Err(err) => {
@@ -88,7 +558,7 @@ fn _read_key(ldbid: u32, resource_id: &str, user_no: u32) {
         }
     };
     println!("Key Data:");
-    print_buffer(&buffer);
+    emit_buffer(&maybe_decompress(buffer), target);
 
 }
 
@@ -175,13 +645,14 @@ fn _get_key_size(ldbid: u32, resource_id: &str, user_no: u32) {
 ///   * Reads the file content using the `read_file()` function from the wrapper..
 ///   * Prints success or error messages.
 ///   * Closes the file using the`close_file()` function from the wrapper.
-///   * Dumps the file content using `print_buffer()`.
-fn _read_file(ldbid: u32, resource_id: &str, user_no: u32){
+///   * Sends the file content to `target`: the console in the requested output format (hex dump
+///     by default), or the `--out-file` file.
+fn _read_file(ldbid: u32, resource_id: &str, user_no: u32, target: &OutputTarget){
     print!("Resource_id: ");
     for c in resource_id.chars() {
         print!("{}", if c.is_ascii() { c } else { '.' });
     }
-    println!(); 
+    println!();
     println!("Reading file: user_no: {}, ldbid: {:X}", user_no, ldbid);
 
     let fd = match open_file(ldbid, resource_id, user_no) {
@@ -204,8 +675,8 @@ fn _read_file(ldbid: u32, resource_id: &str, user_no: u32){
     if let Err(err) = close_file(fd) {
         println!("Failed to close file! Error: {:?}", err);
     }
-    
-    print_buffer(&file_data);
+
+    emit_buffer(&maybe_decompress(file_data), target);
 }
 
 /// Determines the size of a file
@@ -365,9 +836,19 @@ fn print_buffer(buffer: &Vec<u8>) {
 ///   * `-o`, `--operation`: Operation mode (readkey, writekey, deletekey, getkeysize, readfile, writefile, deletefile, getfilesize).
 ///   * `-a`, `--app_name`: Name of the application.
 ///   * `-r`, `--resource_id`: ID of the resource (key or file).
-///   * `-p`, `--payload`: Payload to write (used for write operations).
+///   * `-p`, `--payload`: Payload to write (used for write operations); `-p -` reads it from stdin.
+///   * `--payload-file`: Read the write payload from this file instead of `--payload`.
 ///   * `-u`, `--user_no`: Optional user number (default is `0`).
 ///   * `-l`, `--ldbid`: Optional LDB ID in hexadecimal (default is `0xFF`).
+///   * `--encoding`: How `--payload` is interpreted: `utf8` (default), `hex` or `base64`.
+///   * `--output-format`: How read data is printed: `hexdump` (default), `raw`, `hex` or `base64`.
+///   * `--out-file`: Write retrieved bytes verbatim to this file instead of the console.
+///   * `--compress`: Compress written payloads: `none` (default) or `rle`; reads auto-detect
+///     compressed data regardless of this flag.
+///   * `--batch-file`: For `writekey`/`writefile`, write multiple `resource_id`/payload entries
+///     (one `resource_id<TAB>payload` line each) from this file instead of `-r`/`-p`.
+///   * `--defer-flush`: With `--batch-file`, buffer every entry in memory and commit them all as
+///     one batch at the end instead of writing each as it's parsed.
 ///   * `-h`, `--help`: Prints manual on how to use the CLI Tool.
 ///
 /// Description:
@@ -455,6 +936,48 @@ fn main() -> Result<(), ErrorCode> {
                 .long("ldbid")
                 .help("LDB ID (hex)"),
         )
+        .arg(
+            Arg::new("encoding")
+                .long("encoding")
+                .help("How --payload is interpreted: utf8 (default), hex or base64"),
+        )
+        .arg(
+            Arg::new("output_format")
+                .long("output-format")
+                .help("How read data is printed: hexdump (default), raw, hex or base64"),
+        )
+        .arg(
+            Arg::new("payload_file")
+                .long("payload-file")
+                .help("Read the write payload from this file instead of --payload"),
+        )
+        .arg(
+            Arg::new("out_file")
+                .long("out-file")
+                .help("Write retrieved bytes verbatim to this file instead of the console"),
+        )
+        .arg(
+            Arg::new("compress")
+                .long("compress")
+                .help("Compress written payloads: none (default) or rle; reads auto-detect"),
+        )
+        .arg(
+            Arg::new("batch_file")
+                .long("batch-file")
+                .help(
+                    "Write multiple writekey/writefile entries, one 'resource_id<TAB>payload' \
+                     line per entry, from this file instead of -r/-p",
+                ),
+        )
+        .arg(
+            Arg::new("defer_flush")
+                .long("defer-flush")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "With --batch-file, buffer every entry in memory and commit them all as one \
+                     batch at the end instead of writing each as it's parsed",
+                ),
+        )
         .get_matches();
 
 
@@ -480,53 +1003,65 @@ fn main() -> Result<(), ErrorCode> {
         .and_then(|s| s.parse::<u32>().ok()); 
     let ldbid: Option<u32> = matches
         .get_one::<String>("ldbid")
-        .and_then(|s| u32::from_str_radix(s, 16).ok()); 
+        .and_then(|s| u32::from_str_radix(s, 16).ok());
+    let encoding = PayloadEncoding::parse(matches.get_one::<String>("encoding"));
+    let output_format = OutputFormat::parse(matches.get_one::<String>("output_format"));
+    let payload_file = matches.get_one::<String>("payload_file");
+    let out_file = matches.get_one::<String>("out_file");
+    let output_target = match out_file {
+        Some(path) => OutputTarget::File(path.clone()),
+        None => OutputTarget::Console(output_format),
+    };
+    let compression = CompressionMode::parse(matches.get_one::<String>("compress"));
+    let batch_file = matches.get_one::<String>("batch_file");
+    let defer_flush = matches.get_flag("defer_flush");
 
 
     /*Set Default Values for user_no and ldbid if empty*/
-    let user_no = user_no.unwrap_or(0); 
-    let ldbid = ldbid.unwrap_or(0xFF); 
+    let user_no = user_no.unwrap_or(0);
+    let ldbid = ldbid.unwrap_or(0xFF);
 
     let app_name = app_name.unwrap_or_else(|| {
         println!("Application name is required");
         exit(1);
     });
 
-    let resource_id = resource_id.unwrap_or_else(|| {
-        println!("Resource ID is required");
-        exit(1);
-    });
-
 
     /* Initialize Library */
     init_library(app_name)?;
 
     match op_mode {
         OperationMode::ReadKey => {
-            _read_key(ldbid, resource_id, user_no);
-        }
-        OperationMode::WriteKey => {
-            let buffer = payload.as_ref().map_or("", |s| s.as_str()).as_bytes().to_vec();
-            _write_key(ldbid, resource_id, user_no, buffer);
+            _read_key(ldbid, require_resource_id(resource_id), user_no, &output_target);
         }
+        OperationMode::WriteKey => match batch_file {
+            Some(path) => run_batch_writes(path, false, ldbid, user_no, encoding, compression, defer_flush),
+            None => {
+                let buffer = maybe_compress(resolve_payload(payload, payload_file, encoding), compression);
+                _write_key(ldbid, require_resource_id(resource_id), user_no, buffer);
+            }
+        },
         OperationMode::DeleteKey => {
-            _delete_key(ldbid, resource_id, user_no);
+            _delete_key(ldbid, require_resource_id(resource_id), user_no);
         }
         OperationMode::GetKeySize => {
-            _get_key_size(ldbid, resource_id, user_no);
+            _get_key_size(ldbid, require_resource_id(resource_id), user_no);
         }
         OperationMode::ReadFile => {
-            _read_file(ldbid, resource_id, user_no);
-        }
-        OperationMode::WriteFile => {
-            let buffer = payload.as_ref().map_or("", |s| s.as_str()).as_bytes().to_vec();
-            _write_file(ldbid, resource_id, user_no, buffer);
+            _read_file(ldbid, require_resource_id(resource_id), user_no, &output_target);
         }
+        OperationMode::WriteFile => match batch_file {
+            Some(path) => run_batch_writes(path, true, ldbid, user_no, encoding, compression, defer_flush),
+            None => {
+                let buffer = maybe_compress(resolve_payload(payload, payload_file, encoding), compression);
+                _write_file(ldbid, require_resource_id(resource_id), user_no, buffer);
+            }
+        },
         OperationMode::DeleteFile => {
-            _remove_file(ldbid, resource_id, user_no);
+            _remove_file(ldbid, require_resource_id(resource_id), user_no);
         }
         OperationMode::GetFileSize => {
-            _get_file_size(ldbid, resource_id, user_no);
+            _get_file_size(ldbid, require_resource_id(resource_id), user_no);
         }
         OperationMode::Invalid => {
             println!("Unsupported operation mode");
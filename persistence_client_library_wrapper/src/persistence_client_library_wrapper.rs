@@ -102,19 +102,245 @@ extern crate libloading;
 use libloading::{Library, Symbol};
 use std::ffi::CString;
 
-use std::sync::Mutex; 
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::os::raw::c_char;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use once_cell::sync::Lazy;
 
 mod persistence_client_library_ffi;
+mod pcl_api;
 
-use crate::persistence_client_library_ffi::*; 
+use crate::persistence_client_library_ffi::*;
+use pcl_api::{LiveApi, PclApi};
 
-/// Keep loaded Lib static and secured 
+/// Keep loaded Lib static and secured
 static LIB: Lazy<Mutex<Option<Library>>> = Lazy::new(|| Mutex::new(None));
 
+/// Number of `init_library` calls that have not yet been matched by a `deinit_library` call.
+///
+/// Multiple independent subsystems in the same process may each call `init_library`/
+/// `deinit_library`; only the 0→1 transition actually loads/initializes the underlying
+/// PCL and only the 1→0 transition tears it down, so one subsystem deinitializing does not
+/// break another that is still relying on the library being loaded.
+static INIT_REFCOUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Outcome of [`claim_init_refcount`]
+enum InitClaim {
+    /// `INIT_REFCOUNT` was already positive; this call just joined it and there's nothing left
+    /// to do. Carries the refcount after joining.
+    Joined(usize),
+    /// This call won the race to move `INIT_REFCOUNT` from 0 to 1 and must now actually invoke
+    /// the real `pclInitLibrary`. On failure the caller must undo this via
+    /// [`release_init_claim`].
+    Transition,
+}
+
+/// Atomically claim either a join onto an already-initialized library or the 0→1 transition
+/// itself.
+///
+/// Checking `INIT_REFCOUNT > 0` and then incrementing it in two separate steps (as
+/// `init_library_with_mode`/`init_library_from_path` used to) lets two threads racing while the
+/// refcount is 0 both observe 0 and both invoke the real `pclInitLibrary`, or let an init race a
+/// concurrent `deinit_library` into skipping the real init call entirely. Folding the check and
+/// the claim into one atomic op removes that window: exactly one caller ever sees
+/// [`InitClaim::Transition`] for a given 0→1 crossing.
+fn claim_init_refcount() -> InitClaim {
+    if let Ok(before) = INIT_REFCOUNT.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+        (n > 0).then_some(n + 1)
+    }) {
+        return InitClaim::Joined(before + 1);
+    }
+
+    match INIT_REFCOUNT.compare_exchange(0, 1, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => InitClaim::Transition,
+        // Lost the race for the 0→1 transition: the winner already bumped it past zero, so just
+        // join it like the fast path above.
+        Err(_) => InitClaim::Joined(INIT_REFCOUNT.fetch_add(1, Ordering::AcqRel) + 1),
+    }
+}
+
+/// Undo a claimed 0→1 transition after the real `pclInitLibrary` call failed, so a retry (or a
+/// sibling component's own `init_library`) sees 0 again instead of believing the library is
+/// already initialized.
+fn release_init_claim() {
+    INIT_REFCOUNT.fetch_sub(1, Ordering::AcqRel);
+}
+
+/// Identifies a registered change-notification callback.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct NotifyKey {
+    ldbid: u32,
+    resource_id: String,
+    user_no: u32,
+    seat_no: u32,
+}
+
+/// Registered change-notification callbacks, keyed by the key they were registered on.
+///
+/// The trampoline handed to the C API only carries back `ldbid`/`resource_id`/`reason`, so on
+/// invocation we look up the first entry matching `ldbid`/`resource_id` regardless of
+/// `user_no`/`seat_no`.
+static NOTIFY_CALLBACKS: Lazy<Mutex<HashMap<NotifyKey, Box<dyn FnMut(u32) + Send>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[doc(hidden)]
+/// Trampoline invoked by the C API when a registered key changes; looks up and invokes the
+/// matching Rust closure.
+unsafe extern "C" fn notify_trampoline(ldbid: u32, resource_id: *const c_char, reason: u32) {
+    let resource_id = unsafe { std::ffi::CStr::from_ptr(resource_id) }
+        .to_string_lossy()
+        .into_owned();
+
+    let mut callbacks = match NOTIFY_CALLBACKS.lock() {
+        Ok(callbacks) => callbacks,
+        Err(_) => return,
+    };
+
+    if let Some(callback) = callbacks
+        .iter_mut()
+        .find(|(key, _)| key.ldbid == ldbid && key.resource_id == resource_id)
+        .map(|(_, callback)| callback)
+    {
+        callback(reason);
+    }
+}
+
+#[doc(hidden)]
+fn load_pcl_key_register_notify_on_change() -> Result<PclKeyRegisterNotifyOnChangeFn, ErrorCode> {
+    let lib_lock = LIB.lock().unwrap();
+    let lib = lib_lock.as_ref().ok_or(ErrorCode::LibraryNotLoaded)?;
+
+    unsafe {
+        let func: Symbol<PclKeyRegisterNotifyOnChangeFn> = lib
+            .get(b"pclKeyRegisterNotifyOnChange")
+            .map_err(|_| ErrorCode::FunctionNotFound)?;
+        Ok(*func)
+    }
+}
+
+#[doc(hidden)]
+fn load_pcl_key_unregister_notify_on_change() -> Result<PclKeyUnRegisterNotifyOnChangeFn, ErrorCode> {
+    let lib_lock = LIB.lock().unwrap();
+    let lib = lib_lock.as_ref().ok_or(ErrorCode::LibraryNotLoaded)?;
+
+    unsafe {
+        let func: Symbol<PclKeyUnRegisterNotifyOnChangeFn> = lib
+            .get(b"pclKeyUnRegisterNotifyOnChange")
+            .map_err(|_| ErrorCode::FunctionNotFound)?;
+        Ok(*func)
+    }
+}
+
+/// Registers a callback to be invoked whenever the given key changes.
+///
+/// Features:
+///   * Uses DBus-backed change notifications exposed by the PCL (`pclKeyRegisterNotifyOnChange`).
+///
+/// Parameters:
+///   * `ldbid`: The database ID.
+///   * `resource_id`: The ID of the resource to watch.
+///   * `user_no`: The user number.
+///   * `callback`: Invoked with the PCL-reported change reason whenever the key changes.
+///
+/// Return:
+///   * `Ok(())` if the registration succeeded.
+///   * `Err(ErrorCode::EpersNotifyNotAllowed)` if registration on this key isn't allowed.
+///   * `Err(Errorcode::<CODE>)` with the error code if registration fails.
+pub fn register_notify_on_change<F>(
+    ldbid: u32,
+    resource_id: &str,
+    user_no: u32,
+    callback: F,
+) -> Result<(), ErrorCode>
+where
+    F: FnMut(u32) + Send + 'static,
+{
+    let key = NotifyKey {
+        ldbid,
+        resource_id: resource_id.to_string(),
+        user_no,
+        seat_no: 0,
+    };
+
+    {
+        let callbacks = NOTIFY_CALLBACKS.lock().unwrap();
+        if callbacks.contains_key(&key) {
+            // Guard against re-entrant registration of the same key.
+            return Err(ErrorCode::EpersNotifyNotAllowed);
+        }
+    }
+
+    let pcl_register = load_pcl_key_register_notify_on_change()?;
+    let c_resource_id = CString::new(resource_id).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
+
+    let rval = unsafe {
+        pcl_register(
+            ldbid,
+            c_resource_id.as_ptr(),
+            user_no,
+            key.seat_no,
+            notify_trampoline,
+        )
+    };
+
+    if rval >= 0 {
+        NOTIFY_CALLBACKS
+            .lock()
+            .unwrap()
+            .insert(key, Box::new(callback));
+        Ok(())
+    } else {
+        Err(rval.into())
+    }
+}
+
+/// Unregisters a previously registered change-notification callback.
+///
+/// Parameters:
+///   * `ldbid`: The database ID.
+///   * `resource_id`: The ID of the resource to stop watching.
+///   * `user_no`: The user number.
+///
+/// Return:
+///   * `Ok(())` if unregistration succeeded. The stored closure is dropped.
+///   * `Err(Errorcode::<CODE>)` with the error code if unregistration fails.
+pub fn unregister_notify_on_change(
+    ldbid: u32,
+    resource_id: &str,
+    user_no: u32,
+) -> Result<(), ErrorCode> {
+    let key = NotifyKey {
+        ldbid,
+        resource_id: resource_id.to_string(),
+        user_no,
+        seat_no: 0,
+    };
+
+    let pcl_unregister = load_pcl_key_unregister_notify_on_change()?;
+    let c_resource_id = CString::new(resource_id).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
+
+    let rval = unsafe { pcl_unregister(ldbid, c_resource_id.as_ptr(), user_no, key.seat_no) };
+
+    if rval >= 0 {
+        NOTIFY_CALLBACKS.lock().unwrap().remove(&key);
+        Ok(())
+    } else {
+        Err(rval.into())
+    }
+}
+
+/// Drops all registered change-notification callbacks.
+///
+/// Called on [`deinit_library`] so no stale closures outlive the underlying PCL session.
+fn clear_notify_callbacks() {
+    NOTIFY_CALLBACKS.lock().unwrap().clear();
+}
+
 /// Runtime Error Codes
 #[repr(i32)]
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ErrorCode {
     /// common error, for this error errno will be set
     EpersCommon = -1,
@@ -223,6 +449,10 @@ pub enum ErrorCode {
     /// a datatype conversion failed
     DatatypeConversionFailed,
 
+    /// none of the configured/candidate library paths could be loaded; carries the list of
+    /// paths that were attempted so misconfiguration is diagnosable
+    LibraryLoadExhausted(Vec<String>),
+
     /// unknown error
     Unknown(i32),
 }
@@ -284,19 +514,94 @@ impl From<i32> for ErrorCode {
     }
 }
 
-/// Method that dynamically loads the Persistence Client Library (C-API)
+/// Environment variable that, if set, overrides the library path tried first by [`load_library`].
+pub const PCL_LIBRARY_PATH_ENV: &str = "PCL_LIBRARY_PATH";
+
+/// Default install prefix searched for the PCL shared object.
+const PCL_LIBRARY_DIR: &str = "/usr/lib";
+
+/// Candidate sonames tried in order when no explicit path is configured, from newest to oldest.
+const PCL_LIBRARY_SONAMES: &[&str] = &[
+    "libpersistence_client_library.so.7",
+    "libpersistence_client_library.so.6",
+    "libpersistence_client_library.so",
+];
+
+/// Builds the ordered list of library paths to try: an env var override first, followed by the
+/// default install prefix combined with each candidate soname.
+fn library_candidates() -> Vec<String> {
+    let mut candidates = Vec::new();
+
+    if let Ok(path) = std::env::var(PCL_LIBRARY_PATH_ENV) {
+        candidates.push(path);
+    }
+
+    candidates.extend(
+        PCL_LIBRARY_SONAMES
+            .iter()
+            .map(|soname| format!("{PCL_LIBRARY_DIR}/{soname}")),
+    );
+
+    candidates
+}
+
+/// Method that dynamically loads the Persistence Client Library (C-API), trying the
+/// `PCL_LIBRARY_PATH` environment variable followed by a fallback list of candidate sonames.
 fn load_library() -> Result<(), ErrorCode> {
-    
-    let lib_name = CString::new("/usr/lib/libpersistence_client_library.so.7")
-        .map_err(|_| ErrorCode::DatatypeConversionFailed)?;
-    let lib = unsafe {
-        Library::new(lib_name.to_str().map_err(|_| ErrorCode::DatatypeConversionFailed)?)
-        .map_err(|_| ErrorCode::LoadLibraryFailed)?
-    };
-    let mut lib_lock = LIB.lock().unwrap();
-    *lib_lock = Some(lib);
+    load_library_from_candidates(&library_candidates())
+}
 
-    Ok(())
+/// Loads the Persistence Client Library from an explicit path, bypassing the env var/fallback
+/// search used by [`load_library`].
+fn load_library_from_path(path: &str) -> Result<(), ErrorCode> {
+    load_library_from_candidates(&[path.to_string()])
+}
+
+/// Tries each candidate path in order, returning the first one that loads successfully.
+///
+/// Returns `ErrorCode::LibraryLoadExhausted` carrying every attempted path if none of them load,
+/// so misconfiguration (wrong soname major version, wrong install prefix) is diagnosable.
+fn load_library_from_candidates(candidates: &[String]) -> Result<(), ErrorCode> {
+    for candidate in candidates {
+        let lib_name =
+            CString::new(candidate.as_str()).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
+        let loaded = unsafe {
+            Library::new(
+                lib_name
+                    .to_str()
+                    .map_err(|_| ErrorCode::DatatypeConversionFailed)?,
+            )
+        };
+
+        if let Ok(lib) = loaded {
+            let mut lib_lock = LIB.lock().unwrap();
+            *lib_lock = Some(lib);
+            return Ok(());
+        }
+    }
+
+    Err(ErrorCode::LibraryLoadExhausted(candidates.to_vec()))
+}
+
+/// Environment variable that, if set, points the PCL at a custom plugin configuration file
+/// describing which storage backend plugins (the persistence common object, SQLite, or a custom
+/// backend) it should load while initializing.
+pub const PCL_PLUGIN_CONFIG_ENV: &str = "PCL_PLUGIN_CONFIG_FILE";
+
+/// Points the PCL at a custom plugin configuration file for the next [`init_library`] (or
+/// [`init_library_with_mode`]/[`init_library_from_path`]) call, by setting
+/// [`PCL_PLUGIN_CONFIG_ENV`].
+///
+/// Must be called before initialization: the PCL only reads its plugin configuration while
+/// loading backends during `pclInitLibrary`, so setting this after the library is already
+/// initialized has no effect until the next init/deinit cycle.
+pub fn configure_plugin_config(path: &str) {
+    // SAFETY: expected to run before `init_library` as part of an application's single-threaded
+    // startup sequence; the PCL only reads this var once, while loading backends during
+    // `pclInitLibrary`.
+    unsafe {
+        std::env::set_var(PCL_PLUGIN_CONFIG_ENV, path);
+    }
 }
 
 /*-----------------------Load Library Functions-----------------------*/
@@ -457,6 +762,20 @@ fn load_pcl_file_write_data() -> Result<PclFileWriteDataFn, ErrorCode> {
     }
 }
 
+#[doc(hidden)]
+/// Method that dynamically provides the corresponding Persistence Client Library (C) pcl_file_seek functionality (Libloading)
+fn load_pcl_file_seek() -> Result<PclFileSeekFn, ErrorCode> {
+
+    let lib_lock = LIB.lock().unwrap();
+    let lib = lib_lock.as_ref().ok_or(ErrorCode::LibraryNotLoaded)?;
+
+    unsafe {
+        let pcl_file_seek: Symbol<PclFileSeekFn> = lib.get(b"pclFileSeek")
+            .map_err(|_| ErrorCode::FunctionNotFound)?;
+        Ok(*pcl_file_seek)
+    }
+}
+
 #[doc(hidden)]
 /// Method that dynamically provides the corresponding Persistence Client Library (C) pcl_file_remove functionality (Libloading)
 fn load_pcl_file_remove() -> Result<PclFileRemoveFn, ErrorCode> {
@@ -470,64 +789,280 @@ fn load_pcl_file_remove() -> Result<PclFileRemoveFn, ErrorCode> {
     }
 }
 
+#[doc(hidden)]
+/// Method that dynamically provides the corresponding Persistence Client Library (C) plugin
+/// status query functionality (Libloading).
+fn load_pcl_plugin_status() -> Result<PclPluginStatusFn, ErrorCode> {
+    let lib_lock = LIB.lock().unwrap();
+    let lib = lib_lock.as_ref().ok_or(ErrorCode::LibraryNotLoaded)?;
+
+    unsafe {
+        let pcl_plugin_status: Symbol<PclPluginStatusFn> = lib.get(b"pclPluginStatus")
+            .map_err(|_| ErrorCode::FunctionNotFound)?;
+        Ok(*pcl_plugin_status)
+    }
+}
+
+
+
+/// Shutdown-notification behavior registered with the PCL on `pclInitLibrary`.
+///
+/// This maps to the C `PCL_SHUTDOWN_TYPE_*` flags passed as the second argument of
+/// `pclInitLibrary` and controls whether the application is registered for the
+/// lifecycle/NSM shutdown sequence.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownMode {
+    /// Don't register for any shutdown notification.
+    None = 0,
+    /// Register for the normal shutdown sequence.
+    Normal = 1,
+    /// Register for the fast shutdown sequence.
+    Fast = 2,
+    /// Register for both the normal and fast shutdown sequence.
+    NormalAndFast = 3,
+}
 
+/// Initializes the library with the given application name, opting out of shutdown
+/// notifications.
+///
+/// Features:
+///   * Initializes the library only if it's not already loaded.
+///   * Executes the initialization through the C API.
+///   * Reference-counted: nested calls by multiple components in the same process only invoke
+///     the underlying `pclInitLibrary` on the 0→1 transition. Every successful call must be
+///     matched by exactly one `deinit_library` call.
+///
+/// Parameter:
+///   * `appname`: The application name as a byte slice.
+///
+/// Return:
+///   * `Ok(refcount)` with the number of outstanding `init_library` calls after this one.
+///   * `Err(Errorcode::<CODE>)` if initialization fails, with the error code.
+pub fn init_library(appname: &str) -> Result<usize, ErrorCode> {
+    init_library_with_mode(appname, ShutdownMode::None)
+}
 
 /// Initializes the library with the given application name and shutdown mode.
-/// 
+///
 /// Features:
 ///   * Initializes the library only if it's not already loaded.
 ///   * Executes the initialization through the C API.
+///   * Reference-counted: nested calls by multiple components in the same process only invoke
+///     the underlying `pclInitLibrary` on the 0→1 transition. Every successful call must be
+///     matched by exactly one `deinit_library` call.
 ///
 /// Parameter:
 ///   * `appname`: The application name as a byte slice.
-pub fn init_library(appname: &str) -> Result<(), ErrorCode> {
+///   * `mode`: The shutdown-notification behavior to register with the PCL.
+///
+/// Return:
+///   * `Ok(refcount)` with the number of outstanding `init_library` calls after this one.
+///   * `Err(Errorcode::<CODE>)` if initialization fails, with the error code.
+pub fn init_library_with_mode(appname: &str, mode: ShutdownMode) -> Result<usize, ErrorCode> {
+    if let InitClaim::Joined(count) = claim_init_refcount() {
+        return Ok(count);
+    }
 
+    // We claimed the 0→1 transition above: we're the only caller that will reach this point
+    // until we either succeed (refcount stays claimed) or fail (and release it below).
     if LIB.lock().unwrap().is_none() {
         load_library().map_err(|_| {
+            release_init_claim();
             ErrorCode::LoadLibraryFailed
         })?;
+    }
+
+    let pcl_init_library = load_pcl_init_library().map_err(|e| {
+        release_init_claim();
+        e
+    })?;
 
+    let c_appname = CString::new(appname).map_err(|_| {
+        release_init_claim();
+        ErrorCode::DatatypeConversionFailed
+    })?;
+
+    unsafe {
+        let rval = pcl_init_library(c_appname.as_ptr(), mode as i32);
+        if rval >= 0 {
+            Ok(1)
+        } else {
+            release_init_claim();
+            Err(rval.into())
+        }
     }
-    let pcl_init_library = load_pcl_init_library()?;
+}
 
-    let c_appname = CString::new(appname).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
+/// Initializes the library, loading it from an explicit path instead of the
+/// `PCL_LIBRARY_PATH`/fallback-soname search used by [`init_library`].
+///
+/// Features:
+///   * Reference-counted, same semantics as [`init_library_with_mode`].
+///
+/// Parameter:
+///   * `appname`: The application name as a byte slice.
+///   * `path`: The explicit path to `libpersistence_client_library`.
+///
+/// Return:
+///   * `Ok(refcount)` with the number of outstanding `init_library` calls after this one.
+///   * `Err(ErrorCode::LibraryLoadExhausted)` carrying `path` if it could not be loaded.
+///   * `Err(Errorcode::<CODE>)` if initialization fails, with the error code.
+pub fn init_library_from_path(appname: &str, path: &str) -> Result<usize, ErrorCode> {
+    if let InitClaim::Joined(count) = claim_init_refcount() {
+        return Ok(count);
+    }
+
+    // We claimed the 0→1 transition above: we're the only caller that will reach this point
+    // until we either succeed (refcount stays claimed) or fail (and release it below).
+    if LIB.lock().unwrap().is_none() {
+        load_library_from_path(path).map_err(|e| {
+            release_init_claim();
+            e
+        })?;
+    }
+
+    let pcl_init_library = load_pcl_init_library().map_err(|e| {
+        release_init_claim();
+        e
+    })?;
+
+    let c_appname = CString::new(appname).map_err(|_| {
+        release_init_claim();
+        ErrorCode::DatatypeConversionFailed
+    })?;
 
     unsafe {
-        let rval = pcl_init_library(c_appname.as_ptr(), 0);
+        let rval = pcl_init_library(c_appname.as_ptr(), ShutdownMode::None as i32);
         if rval >= 0 {
-            Ok(())
+            Ok(1)
         } else {
-            Err(rval.into()) 
+            release_init_claim();
+            Err(rval.into())
+        }
+    }
+}
+
+/// Reports which storage backend plugins the PCL actually loaded, as the raw bitmask returned by
+/// the C `pclPluginStatus` query.
+///
+/// Intended to be called after [`init_library`] (or one of its variants) to validate that a
+/// custom backend configured via [`configure_plugin_config`] actually came up, rather than
+/// discovering a misconfigured backend only once reads/writes against it start failing.
+///
+/// Return:
+///   * `Ok(mask)` with the bitmask of loaded backend plugins.
+///   * `Err(ErrorCode::EpersNoPluginFcnT)`, `Err(ErrorCode::EpersDlopenError)`,
+///     `Err(ErrorCode::EpersNoPluginFunct)`, `Err(ErrorCode::EpersNoPluginFunctAvail)` or
+///     `Err(ErrorCode::EpersNoPluginVar)` if a specific plugin failure was reported, rather than a
+///     generic initialization error.
+///   * `Err(Errorcode::<CODE>)` for any other failure code reported by the query.
+pub fn query_plugin_status() -> Result<i32, ErrorCode> {
+    let pcl_plugin_status = load_pcl_plugin_status()?;
+
+    unsafe {
+        let rval = pcl_plugin_status();
+        if rval >= 0 {
+            Ok(rval)
+        } else {
+            Err(rval.into())
         }
     }
 }
 
 /// Deinitializes the library.
-/// 
+///
 /// Features:
 ///   * Deinitializes the library if it was previously initialized.
 ///   * Handles C API call for deinitialization.
-/// 
+///   * Reference-counted: only the 1→0 transition actually invokes `pclDeinitLibrary`, so a
+///     component deinitializing does not tear the library down from under a sibling component
+///     that is still using it.
+///
 /// Return:
-///   * `Ok(())` if deinitialization succeeds.
+///   * `Ok(refcount)` with the number of outstanding `init_library` calls after this one.
 ///   * `Err(Errorcode::<CODE>)` if deinitialization fails, with the error code.
-pub fn deinit_library() -> Result<(), ErrorCode> {
+pub fn deinit_library() -> Result<usize, ErrorCode> {
+    // Refuse to take the stored counter below zero, atomically: a deinit_library() call without
+    // a matching init_library() must not wrap it to usize::MAX, which would otherwise make every
+    // future init_library()/deinit_library() take the reference-counted fast path forever and
+    // never touch the real library again. There's nothing to tear down in that case, so this
+    // also means an unbalanced call doesn't need the library loaded at all.
+    let before = match INIT_REFCOUNT.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1)) {
+        Ok(before) => before,
+        Err(_) => {
+            eprintln!("warning: deinit_library() called without a matching init_library()");
+            return Ok(0);
+        }
+    };
+
+    // Matches a nested init_library() call - nothing left to tear down yet.
+    if before > 1 {
+        return Ok(before - 1);
+    }
 
     if LIB.lock().unwrap().is_none() {
         load_library().map_err(|_| {
-            ErrorCode::LoadLibraryFailed 
+            ErrorCode::LoadLibraryFailed
         })?;
     }
+
     let pcl_deinit_library = load_pcl_deinit_library()?;
 
     unsafe {
         let rval = pcl_deinit_library();
         if rval >= 0 {
-            Ok(())
+            clear_notify_callbacks();
+            Ok(before - 1)
         } else {
-            Err(rval.into()) 
+            // Teardown failed: restore the count we optimistically took, so a retry (or a
+            // sibling component's own deinit_library()) doesn't think it's already torn down.
+            INIT_REFCOUNT.fetch_add(1, Ordering::AcqRel);
+            Err(rval.into())
+        }
+    }
+}
+
+/// LDBID value reserved for data shared across applications, as opposed to an application-local
+/// database.
+pub const LDBID_SHARED: u32 = 0xFF;
+
+/// Identifies the scope a key or file resource is addressed in: which logical database, which
+/// user and which seat.
+///
+/// Every key/file function ultimately resolves to a `(ldbid, user_no, seat_no)` triple passed to
+/// the C API; grouping them avoids repeating the same three arguments at every call site and
+/// makes per-seat and shared (cross-application, `LDBID_SHARED`) data reachable instead of being
+/// silently pinned to seat `0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceAddress {
+    /// The logical database ID, or [`LDBID_SHARED`] for cross-application shared data.
+    pub ldbid: u32,
+    /// The user number.
+    pub user_no: u32,
+    /// The seat number.
+    pub seat_no: u32,
+}
+
+impl ResourceAddress {
+    /// Creates a new scope addressing a specific seat.
+    pub fn new(ldbid: u32, user_no: u32, seat_no: u32) -> Self {
+        Self {
+            ldbid,
+            user_no,
+            seat_no,
         }
     }
+
+    /// Creates a scope for application-local data on seat `0`.
+    pub fn local(ldbid: u32, user_no: u32) -> Self {
+        Self::new(ldbid, user_no, 0)
+    }
+
+    /// Creates a scope for data shared across applications (`LDBID_SHARED`) on seat `0`.
+    pub fn shared(user_no: u32) -> Self {
+        Self::new(LDBID_SHARED, user_no, 0)
+    }
 }
 
 /*-----------------------Library Functions-----------------------*/
@@ -547,29 +1082,30 @@ pub fn deinit_library() -> Result<(), ErrorCode> {
 ///   * `Ok(buffer)` containing the key data if the read is successful.
 ///   * `Err(Errorcode::<CODE>)` with the error code if the read fails.
 pub fn read_key(ldbid: u32, resource_id: &str, user_no: u32) -> Result<Vec<u8>, ErrorCode> {
+    read_key_scoped(&ResourceAddress::local(ldbid, user_no), resource_id)
+}
 
-    let size = get_key_size(ldbid, resource_id, user_no)? as i32;
-    let mut buffer = vec![0u8; (size) as usize];
-    let pcl_key_read_data = load_pcl_key_read_data()?;
+/// Reads a key for an explicit seat/scope.
+///
+/// Features:
+///   * Reads the key data through the C API, threading the real `seat_no` through instead of
+///     always passing `0`.
+///
+/// Parameters:
+///   * `scope`: The `(ldbid, user_no, seat_no)` scope to read the key in.
+///   * `resource_id`: The ID of the resource to read.
+///
+/// Return:
+///   * `Ok(buffer)` containing the key data if the read is successful.
+///   * `Err(Errorcode::<CODE>)` with the error code if the read fails.
+pub fn read_key_scoped(scope: &ResourceAddress, resource_id: &str) -> Result<Vec<u8>, ErrorCode> {
 
-    let c_resource_id = CString::new(resource_id).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
+    let size = get_key_size_scoped(scope, resource_id)? as usize;
+    let mut buffer = vec![0u8; size];
 
-    unsafe {
-        let rval = pcl_key_read_data(
-            ldbid,
-            c_resource_id.as_ptr(),
-            user_no,
-            0, /*seat_no:  Jump over Seat-No from C-API*/
-            buffer.as_mut_ptr(),
-            size,
-        );
+    LiveApi.key_read_data(scope.ldbid, resource_id, scope.user_no, scope.seat_no, &mut buffer)?;
 
-        if rval >= 0 {
-            Ok(buffer)
-        } else {
-            Err(rval.into())
-        }
-    }
+    Ok(buffer)
 }
 
 /// Gets the size of a key
@@ -586,22 +1122,62 @@ pub fn read_key(ldbid: u32, resource_id: &str, user_no: u32) -> Result<Vec<u8>,
 ///   * `Ok(rval)` containing the key size if successful.
 ///   * `Err(Errorcode::<CODE>)` with the error code if the operation fails.
 pub fn get_key_size(ldbid: u32, resource_id: &str, user_no: u32) -> Result<i32, ErrorCode> {
-    
-    let pcl_key_get_size = load_pcl_key_get_size()?;
-    let c_resource_id = CString::new(resource_id).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
+    get_key_size_scoped(&ResourceAddress::local(ldbid, user_no), resource_id)
+}
 
-    unsafe {
-        let rval = pcl_key_get_size(
-            ldbid, 
-            c_resource_id.as_ptr(), 
-            user_no, 
-            0 /*seat_no:  Jump over Seat-No from C-API*/
-        );
-        if rval >= 0 {
-            Ok(rval)
-        } else {
-            Err(rval.into())
-        }
+/// Gets the size of a key for an explicit seat/scope.
+///
+/// Features:
+///   * Calls the C API to fetch the key size, threading the real `seat_no` through instead of
+///     always passing `0`.
+///
+/// Parameters:
+///   * `scope`: The `(ldbid, user_no, seat_no)` scope to query.
+///   * `resource_id`: The ID of the resource.
+///
+/// Return:
+///   * `Ok(rval)` containing the key size if successful.
+///   * `Err(Errorcode::<CODE>)` with the error code if the operation fails.
+pub fn get_key_size_scoped(scope: &ResourceAddress, resource_id: &str) -> Result<i32, ErrorCode> {
+    LiveApi.key_get_size(scope.ldbid, resource_id, scope.user_no, scope.seat_no)
+}
+
+/// Checks whether a key exists
+///
+/// Features:
+///   * Calls the C API to fetch the key size and treats `EpersNokey` as a clean `false` instead
+///     of an error.
+///
+/// Parameters:
+///   * `ldbid`: The database ID.
+///   * `resource_id`: The ID of the resource.
+///   * `user_no`: The user number.
+///
+/// Return:
+///   * `Ok(true)` if the key exists, `Ok(false)` if it doesn't.
+///   * `Err(Errorcode::<CODE>)` with the error code if the existence check itself fails.
+pub fn key_exists(ldbid: u32, resource_id: &str, user_no: u32) -> Result<bool, ErrorCode> {
+    key_exists_scoped(&ResourceAddress::local(ldbid, user_no), resource_id)
+}
+
+/// Checks whether a key exists for an explicit seat/scope.
+///
+/// Features:
+///   * Calls the C API to fetch the key size, threading the real `seat_no` through instead of
+///     always passing `0`, and treats `EpersNokey` as a clean `false` instead of an error.
+///
+/// Parameters:
+///   * `scope`: The `(ldbid, user_no, seat_no)` scope to query.
+///   * `resource_id`: The ID of the resource.
+///
+/// Return:
+///   * `Ok(true)` if the key exists, `Ok(false)` if it doesn't.
+///   * `Err(Errorcode::<CODE>)` with the error code if the existence check itself fails.
+pub fn key_exists_scoped(scope: &ResourceAddress, resource_id: &str) -> Result<bool, ErrorCode> {
+    match get_key_size_scoped(scope, resource_id) {
+        Ok(_) => Ok(true),
+        Err(ErrorCode::EpersNokey) => Ok(false),
+        Err(err) => Err(err),
     }
 }
 
@@ -620,25 +1196,29 @@ pub fn get_key_size(ldbid: u32, resource_id: &str, user_no: u32) -> Result<i32,
 ///   * `Ok(())` if successful.
 ///   * `Err(Errorcode::<CODE>)` with the error code if the write fails.
 pub fn write_key(ldbid: u32, resource_id: &str, user_no: u32, buffer: Vec<u8>) -> Result<(), ErrorCode> {
+    write_key_scoped(&ResourceAddress::local(ldbid, user_no), resource_id, buffer)
+}
 
-    let pcl_key_write_data = load_pcl_key_write_data()?;
-    let c_resource_id = CString::new(resource_id).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
-
-    unsafe {
-        let rval = pcl_key_write_data(
-            ldbid, 
-            c_resource_id.as_ptr(), 
-            user_no, 
-            0, /*seat_no:  Jump over Seat-No from C-API*/
-            buffer.as_ptr(), 
-            buffer.len() as i32
-        );
-        if rval >= 0 {
-            Ok(())
-        } else {
-            Err(rval.into())
-        }
-    }
+/// Writes a key for an explicit seat/scope, including shared (cross-application) data.
+///
+/// Features:
+///   * Writes the key data through the C API, threading the real `seat_no` through instead of
+///     always passing `0`.
+///
+/// Parameters:
+///   * `scope`: The `(ldbid, user_no, seat_no)` scope to write the key in.
+///   * `resource_id`: The ID of the resource to write.
+///   * `buffer`: The data buffer to write.
+///
+/// Return:
+///   * `Ok(())` if successful.
+///   * `Err(Errorcode::<CODE>)` with the error code if the write fails.
+pub fn write_key_scoped(
+    scope: &ResourceAddress,
+    resource_id: &str,
+    buffer: Vec<u8>,
+) -> Result<(), ErrorCode> {
+    LiveApi.key_write_data(scope.ldbid, resource_id, scope.user_no, scope.seat_no, &buffer)
 }
 
 /// Deletes a key
@@ -655,31 +1235,40 @@ pub fn write_key(ldbid: u32, resource_id: &str, user_no: u32, buffer: Vec<u8>) -
 ///   * `Ok(())` if successful.
 ///   * `Err(Errorcode::<CODE>)` with the error code if the delete operation fails.
 pub fn delete_key(ldbid: u32, resource_id: &str, user_no: u32) -> Result<(), ErrorCode> {
-    
-    let pcl_key_delete = load_pcl_key_delete()?;
-    let c_resource_id = CString::new(resource_id).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
+    delete_key_scoped(&ResourceAddress::local(ldbid, user_no), resource_id)
+}
 
-    unsafe {
-        let rval = pcl_key_delete(
-            ldbid, 
-            c_resource_id.as_ptr(), 
-            user_no, 
-            0 /*seat_no:  Jump over Seat-No from C-API*/
-        );
-        if rval >= 0 {
-            Ok(())
-        } else {
-            Err(rval.into())
-        }
-    }
+/// Deletes a key for an explicit seat/scope.
+///
+/// Features:
+///   * Deletes the key through the C API, threading the real `seat_no` through instead of always
+///     passing `0`.
+///
+/// Parameters:
+///   * `scope`: The `(ldbid, user_no, seat_no)` scope to delete the key from.
+///   * `resource_id`: The ID of the resource to delete.
+///
+/// Return:
+///   * `Ok(())` if successful.
+///   * `Err(Errorcode::<CODE>)` with the error code if the delete operation fails.
+pub fn delete_key_scoped(scope: &ResourceAddress, resource_id: &str) -> Result<(), ErrorCode> {
+    LiveApi.key_delete(scope.ldbid, resource_id, scope.user_no, scope.seat_no)
 }
 
 /*-------File Handling Functions-------*/
-/// Reads data from a file
+
+/// Largest single `pclFileReadData` call issued by [`read_file`], so one giant read never has to
+/// ask the C API for more than this many bytes at once.
+const PCL_MAX_READ_CHUNK: usize = 1024 * 1024;
+
+/// Reads the whole remaining content of a file, growing the destination buffer as data comes in.
 ///
 /// Features:
-///   * Reads the file data based on the provided file descriptor.
-///   * Uses the C API to read the file into a buffer.
+///   * Uses `get_file_size` only as a capacity hint, not as the read length: a resource that
+///     grows between the size query and the read below is no longer truncated, and a
+///     negative/overflowed `i32` size (resources at or above 2 GiB) no longer aborts the read.
+///   * Reads in bounded chunks (capped at [`PCL_MAX_READ_CHUNK`]) into the spare capacity of a
+///     geometrically-growing `Vec<u8>`, modeled on std's `read_to_end`, until a read returns 0.
 ///
 /// Parameters:
 ///   * `fd`: The file descriptor to read from.
@@ -689,23 +1278,34 @@ pub fn delete_key(ldbid: u32, resource_id: &str, user_no: u32) -> Result<(), Err
 ///   * `Err(Errorcode::<CODE>)` with the error code if the read operation fails.
 pub fn read_file(fd: i32) -> Result<Vec<u8>, ErrorCode> {
 
-    let size = get_file_size(fd)? as i32;
-    let mut buffer = vec![0u8; size as usize];
-    let pcl_file_read_data = load_pcl_file_read_data()?;
+    let size_hint = get_file_size(fd).unwrap_or(0).max(0) as usize;
+    let mut buffer = Vec::with_capacity(size_hint.min(PCL_MAX_READ_CHUNK));
 
-    unsafe {
-        let rval = pcl_file_read_data(
-            fd,
-            buffer.as_mut_ptr(),
-            size,
-        );
+    loop {
+        if buffer.len() == buffer.capacity() {
+            buffer.reserve(buffer.capacity().max(PCL_MAX_READ_CHUNK));
+        }
 
-        if rval >= 0 {
-            Ok(buffer)
-        } else {
-            Err(rval.into())
+        let start = buffer.len();
+        let chunk_len = (buffer.capacity() - start).min(PCL_MAX_READ_CHUNK);
+        buffer.resize(start + chunk_len, 0);
+
+        let read = match LiveApi.file_read_data(fd, &mut buffer[start..start + chunk_len]) {
+            Ok(read) => read as usize,
+            Err(err) => {
+                buffer.truncate(start);
+                return Err(err);
+            }
+        };
+
+        buffer.truncate(start + read);
+
+        if read == 0 {
+            break;
         }
     }
+
+    Ok(buffer)
 }
 
 /// Gets the size of a file
@@ -720,17 +1320,7 @@ pub fn read_file(fd: i32) -> Result<Vec<u8>, ErrorCode> {
 ///   * `Ok(rval)` containing the file size if successful.
 ///   * `Err(Errorcode::<CODE>)` with the error code if the operation fails.
 pub fn get_file_size(fd: i32) -> Result<i32, ErrorCode> {
-    
-    let pcl_file_get_size = load_pcl_file_get_size()?;
-    
-    unsafe {
-        let rval = pcl_file_get_size(fd);
-        if rval >= 0 {
-            Ok(rval)
-        } else {
-            Err(rval.into())
-        }
-    }
+    LiveApi.file_get_size(fd)
 }
 
 /// Opens a file in the library
@@ -747,22 +1337,100 @@ pub fn get_file_size(fd: i32) -> Result<i32, ErrorCode> {
 ///   * `Ok(fd)` containing the file descriptor if successful.
 ///   * `Err(Errorcode::<CODE>)` with the error code if the operation fails.
 pub fn open_file(ldbid: u32, resource_id: &str, user_no: u32) -> Result<i32, ErrorCode> {
-    
-    let pcl_file_open = load_pcl_file_open()?;
-    let c_resource_id = CString::new(resource_id).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
+    open_file_scoped(&ResourceAddress::local(ldbid, user_no), resource_id)
+}
 
-    unsafe {
-        let fd = pcl_file_open(
-            ldbid,
-            c_resource_id.as_ptr(),
-            user_no,
-            0, /*seat_no:  Jump over Seat-No from C-API*/
-        );
-        if fd >= 0 {
-            Ok(fd)
-        } else {
-            Err(fd.into()) /*If fd < 0, it contains an error value */
-        }
+/// Opens a file in the library for an explicit seat/scope.
+///
+/// Features:
+///   * Uses the C API to open the file and return a file descriptor, threading the real
+///     `seat_no` through instead of always passing `0`.
+///
+/// Parameters:
+///   * `scope`: The `(ldbid, user_no, seat_no)` scope to open the file in.
+///   * `resource_id`: The ID of the resource to open.
+///
+/// Return:
+///   * `Ok(fd)` containing the file descriptor if successful.
+///   * `Err(Errorcode::<CODE>)` with the error code if the operation fails.
+pub fn open_file_scoped(scope: &ResourceAddress, resource_id: &str) -> Result<i32, ErrorCode> {
+    LiveApi.file_open(scope.ldbid, resource_id, scope.user_no, scope.seat_no)
+}
+
+/// Builder mirroring `std::fs::OpenOptions` for opening a PCL-backed resource.
+///
+/// The current `pclFileOpen` C API takes no open-mode flags of its own, so this builder cannot
+/// yet change what the PCL does on open — every combination opens the resource the same way
+/// [`open_file_scoped`] always has. It exists so callers can already express read/write/append/
+/// truncate/create intent at the call site, and so that intent only needs to be wired through to
+/// the C call in one place whenever a future PCL version exposes open-mode flags.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PclOpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+    custom_flags: i32,
+}
+
+impl PclOpenOptions {
+    /// Returns a builder with every option unset.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the option for read access.
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    /// Sets the option for write access.
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    /// Sets the option for appending to the resource instead of overwriting from the start.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// Sets the option to truncate the resource to zero length once opened.
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Sets the option to create the resource if it does not already exist.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// Sets the option to create the resource, failing if it already exists.
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// System-specific escape hatch for PCL open flags not modeled above; OR'd into the flags
+    /// bitmask once the underlying C API grows one.
+    pub fn custom_flags(&mut self, flags: i32) -> &mut Self {
+        self.custom_flags = flags;
+        self
+    }
+
+    /// Opens `resource_id` under `scope` with the accumulated options.
+    ///
+    /// Return:
+    ///   * `Ok(fd)` containing the file descriptor if successful.
+    ///   * `Err(Errorcode::<CODE>)` with the error code if the operation fails.
+    pub fn open(&self, scope: &ResourceAddress, resource_id: &str) -> Result<i32, ErrorCode> {
+        open_file_scoped(scope, resource_id)
     }
 }
 
@@ -780,21 +1448,61 @@ pub fn open_file(ldbid: u32, resource_id: &str, user_no: u32) -> Result<i32, Err
 ///   * `Ok(())` if the write is successful.
 ///   * `Err(Errorcode::<CODE>)` with the error code if the write operation fails.
 pub fn write_file(fd: i32, buffer: Vec<u8>) -> Result<(), ErrorCode> {
-    
-    let pcl_file_write_data = load_pcl_file_write_data()?;
+    LiveApi.file_write_data(fd, &buffer).map(|_| ())
+}
 
-    unsafe {
-        let rval = pcl_file_write_data(
-            fd,
-            buffer.as_ptr(),
-            buffer.len() as i32,
-        );
-        if rval >= 0 {
-            Ok(())
-        } else {
-            Err(rval.into())
-        }
-    }
+/// `whence` value accepted by `pclFileSeek`, mirroring POSIX `lseek`'s `SEEK_SET`.
+const PCL_SEEK_SET: i32 = 0;
+
+/// Reads up to `buf.len()` bytes starting at `offset`.
+///
+/// Unlike `pread`, this moves the descriptor's file position: it is left just past the bytes
+/// read, so a later [`read_file`] on the same `fd` continues from there.
+///
+/// Features:
+///   * The PCL C API only exposes a file-position-relative `pclFileReadData`, so this seeks to
+///     `offset` via `pclFileSeek` first and then reads directly into `buf`.
+///
+/// Parameters:
+///   * `fd`: The file descriptor.
+///   * `buf`: Destination slice; at most `buf.len()` bytes are read.
+///   * `offset`: Byte offset to read from. `offset + buf.len()` must not overflow the file's
+///     `i32` size limit.
+///
+/// Return:
+///   * `Ok(n)` with the number of bytes actually transferred.
+///   * `Err(Errorcode::<CODE>)` with the error code if either the seek or the read fails.
+pub fn read_at(fd: i32, buf: &mut [u8], offset: i64) -> Result<usize, ErrorCode> {
+    let offset = i32::try_from(offset).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
+
+    LiveApi.file_seek(fd, offset, PCL_SEEK_SET)?;
+    let read = LiveApi.file_read_data(fd, buf)?;
+    Ok(read as usize)
+}
+
+/// Writes up to `buf.len()` bytes starting at `offset`.
+///
+/// Unlike `pwrite`, this moves the descriptor's file position: it is left just past the bytes
+/// written.
+///
+/// Features:
+///   * Seeks to `offset` via `pclFileSeek` before writing, for the same reason as [`read_at`].
+///
+/// Parameters:
+///   * `fd`: The file descriptor.
+///   * `buf`: Source slice to write.
+///   * `offset`: Byte offset to write at. `offset + buf.len()` must not overflow the file's
+///     `i32` size limit.
+///
+/// Return:
+///   * `Ok(n)` with the number of bytes actually transferred.
+///   * `Err(Errorcode::<CODE>)` with the error code if either the seek or the write fails.
+pub fn write_at(fd: i32, buf: &[u8], offset: i64) -> Result<usize, ErrorCode> {
+    let offset = i32::try_from(offset).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
+
+    LiveApi.file_seek(fd, offset, PCL_SEEK_SET)?;
+    let written = LiveApi.file_write_data(fd, buf)?;
+    Ok(written as usize)
 }
 
 /// Closes a file
@@ -809,17 +1517,7 @@ pub fn write_file(fd: i32, buffer: Vec<u8>) -> Result<(), ErrorCode> {
 ///   * `Ok(())` if the file is closed successfully.
 ///   * `Err(Errorcode::<CODE>)` with the error code if the close operation fails.
 pub fn close_file(fd: i32) -> Result<(), ErrorCode> {
-    
-    let pcl_file_close = load_pcl_file_close()?;
-
-    unsafe {
-        let rval = pcl_file_close(fd);
-        if rval >= 0 {
-            Ok(())
-        } else {
-            Err(rval.into())
-        }
-    }
+    LiveApi.file_close(fd)
 }
 
 /// Removes a file
@@ -836,21 +1534,334 @@ pub fn close_file(fd: i32) -> Result<(), ErrorCode> {
 ///   * `Ok(())` if the file is removed successfully.
 ///   * `Err(Errorcode::<CODE>)` with the error code if the remove operation fails.
 pub fn remove_file(ldbid: u32, resource_id: &str, user_no: u32) -> Result<(), ErrorCode> {
-    
-    let pcl_file_remove = load_pcl_file_remove()?;
-    let c_resource_id = CString::new(resource_id).map_err(|_| ErrorCode::DatatypeConversionFailed)?;
+    remove_file_scoped(&ResourceAddress::local(ldbid, user_no), resource_id)
+}
 
-    unsafe {
-        let rval = pcl_file_remove(
-            ldbid,
-            c_resource_id.as_ptr(),
-            user_no,
-            0 /*seat_no:  Jump over Seat-No from C-API*/
+/// Removes a file for an explicit seat/scope.
+///
+/// Features:
+///   * Uses the C API to remove the file, threading the real `seat_no` through instead of
+///     always passing `0`.
+///
+/// Parameters:
+///   * `scope`: The `(ldbid, user_no, seat_no)` scope to remove the file from.
+///   * `resource_id`: The ID of the resource to remove.
+///
+/// Return:
+///   * `Ok(())` if the file is removed successfully.
+///   * `Err(Errorcode::<CODE>)` with the error code if the remove operation fails.
+pub fn remove_file_scoped(scope: &ResourceAddress, resource_id: &str) -> Result<(), ErrorCode> {
+    LiveApi.file_remove(scope.ldbid, resource_id, scope.user_no, scope.seat_no)
+}
+
+/*-------Ergonomic RAII/handle wrappers-------*/
+
+/// Owns an open PCL file descriptor and closes it automatically on drop.
+///
+/// This turns the raw `i32` fd returned by [`open_file`] into a leak-resistant handle: an early
+/// return or `?` between open and close can no longer forget to call [`close_file`].
+pub struct PersistentFile {
+    fd: i32,
+    scope: ResourceAddress,
+    resource_id: String,
+}
+
+impl PersistentFile {
+    /// Opens the resource and wraps the resulting descriptor.
+    pub fn open(ldbid: u32, resource_id: &str, user_no: u32) -> Result<Self, ErrorCode> {
+        let scope = ResourceAddress::local(ldbid, user_no);
+        let fd = open_file_scoped(&scope, resource_id)?;
+        Ok(Self {
+            fd,
+            scope,
+            resource_id: resource_id.to_string(),
+        })
+    }
+
+    /// Reads the whole content of the file.
+    pub fn read(&self) -> Result<Vec<u8>, ErrorCode> {
+        read_file(self.fd)
+    }
+
+    /// Writes `buffer` to the file.
+    pub fn write(&self, buffer: Vec<u8>) -> Result<(), ErrorCode> {
+        write_file(self.fd, buffer)
+    }
+
+    /// Returns the size of the file in bytes.
+    pub fn size(&self) -> Result<i32, ErrorCode> {
+        get_file_size(self.fd)
+    }
+
+    /// Closes and removes the file.
+    pub fn remove(self) -> Result<(), ErrorCode> {
+        let scope = self.scope;
+        let resource_id = self.resource_id.clone();
+        drop(self);
+        remove_file_scoped(&scope, &resource_id)
+    }
+
+    /// Returns the underlying fd without affecting ownership.
+    pub fn as_raw(&self) -> i32 {
+        self.fd
+    }
+
+    /// Releases ownership of the underlying fd and returns it without closing it, for callers
+    /// that need to hand the descriptor across an FFI boundary without triggering the
+    /// close-on-drop.
+    pub fn into_raw(self) -> i32 {
+        let fd = self.fd;
+        mem::forget(self);
+        fd
+    }
+}
+
+impl Drop for PersistentFile {
+    fn drop(&mut self) {
+        // Best-effort: there's no sensible way to surface a close error from a Drop impl.
+        let _ = close_file(self.fd);
+    }
+}
+
+/// A handle bound to a fixed `(ldbid, user_no[, seat_no])` scope, so that scope need not be
+/// repeated on every key access.
+pub struct KeyValueStore {
+    scope: ResourceAddress,
+}
+
+impl KeyValueStore {
+    /// Creates a handle bound to the given application-local scope (seat `0`).
+    pub fn new(ldbid: u32, user_no: u32) -> Self {
+        Self::with_scope(ResourceAddress::local(ldbid, user_no))
+    }
+
+    /// Creates a handle bound to an explicit scope.
+    pub fn with_scope(scope: ResourceAddress) -> Self {
+        Self { scope }
+    }
+
+    /// Reads the value for `resource_id` within this handle's scope.
+    pub fn get(&self, resource_id: &str) -> Result<Vec<u8>, ErrorCode> {
+        read_key_scoped(&self.scope, resource_id)
+    }
+
+    /// Writes `buffer` as the value for `resource_id` within this handle's scope.
+    pub fn set(&self, resource_id: &str, buffer: Vec<u8>) -> Result<(), ErrorCode> {
+        write_key_scoped(&self.scope, resource_id, buffer)
+    }
+
+    /// Deletes the value for `resource_id` within this handle's scope.
+    pub fn delete(&self, resource_id: &str) -> Result<(), ErrorCode> {
+        delete_key_scoped(&self.scope, resource_id)
+    }
+
+    /// Returns the size of the value for `resource_id` within this handle's scope.
+    pub fn size(&self, resource_id: &str) -> Result<i32, ErrorCode> {
+        get_key_size_scoped(&self.scope, resource_id)
+    }
+
+    /// Checks whether `resource_id` exists within this handle's scope.
+    pub fn exists(&self, resource_id: &str) -> Result<bool, ErrorCode> {
+        key_exists_scoped(&self.scope, resource_id)
+    }
+}
+
+/*-------Virtual descriptor table-------*/
+
+/// Virtual descriptors below this value are reserved, mirroring the convention of leaving the
+/// standard stdin/stdout/stderr range untouched.
+const FILE_HANDLER_MIN_VFD: i32 = 3;
+
+/// What kind of resource a [`PclFileHandle`] backs.
+///
+/// Only `File` is implemented today; the variant leaves room to add a `Directory` kind later
+/// without another breaking change to [`FileHandler`]'s API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PclFileHandleKind {
+    /// A single opened resource.
+    File,
+}
+
+/// Bookkeeping [`FileHandler`] keeps per virtual descriptor: the backing PCL fd plus the open
+/// metadata needed to reason about the handle without going back to the C API.
+#[derive(Debug, Clone)]
+pub struct PclFileHandle {
+    fd: i32,
+    writable: bool,
+    ldbid: u32,
+    resource_id: String,
+    user_no: u32,
+    kind: PclFileHandleKind,
+}
+
+impl PclFileHandle {
+    /// The backing PCL file descriptor.
+    pub fn fd(&self) -> i32 {
+        self.fd
+    }
+
+    /// Whether this handle was opened for writing.
+    pub fn writable(&self) -> bool {
+        self.writable
+    }
+
+    /// The resource ID this handle was opened with.
+    pub fn resource_id(&self) -> &str {
+        &self.resource_id
+    }
+
+    /// The database ID this handle was opened with.
+    pub fn ldbid(&self) -> u32 {
+        self.ldbid
+    }
+
+    /// The user number this handle was opened with.
+    pub fn user_no(&self) -> u32 {
+        self.user_no
+    }
+
+    /// What kind of resource this handle backs.
+    pub fn kind(&self) -> PclFileHandleKind {
+        self.kind
+    }
+}
+
+/// Tracks open PCL resources behind stable virtual descriptors instead of bare fds, so higher
+/// layers can enumerate what's open and double-close becomes a clean [`ErrorCode`] instead of UB.
+///
+/// Allocates new virtual descriptors with a lowest-free-id-at-or-above-[`FILE_HANDLER_MIN_VFD`] policy,
+/// the same approach used by fd-table shims elsewhere in the Rust ecosystem (e.g. Miri's fs
+/// shim), so closed ids get reused rather than growing without bound.
+#[derive(Debug, Default)]
+pub struct FileHandler {
+    handles: BTreeMap<i32, PclFileHandle>,
+}
+
+impl FileHandler {
+    /// Creates an empty descriptor table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finds the lowest unused virtual descriptor at or above [`FILE_HANDLER_MIN_VFD`].
+    fn allocate_vfd(&self) -> i32 {
+        let mut candidate = FILE_HANDLER_MIN_VFD;
+        while self.handles.contains_key(&candidate) {
+            candidate += 1;
+        }
+        candidate
+    }
+
+    /// Opens `resource_id` and returns a newly allocated virtual descriptor for it.
+    pub fn open(
+        &mut self,
+        ldbid: u32,
+        resource_id: &str,
+        user_no: u32,
+        writable: bool,
+    ) -> Result<i32, ErrorCode> {
+        let fd = open_file(ldbid, resource_id, user_no)?;
+        let vfd = self.allocate_vfd();
+        self.handles.insert(
+            vfd,
+            PclFileHandle {
+                fd,
+                writable,
+                ldbid,
+                resource_id: resource_id.to_string(),
+                user_no,
+                kind: PclFileHandleKind::File,
+            },
         );
-        if rval >= 0 {
-            Ok(())
-        } else {
-            Err(rval.into())
+        Ok(vfd)
+    }
+
+    /// Looks up the handle behind a virtual descriptor.
+    ///
+    /// Return:
+    ///   * `Ok(handle)` if `vfd` is currently open.
+    ///   * `Err(ErrorCode::EpersInvalidHandle)` if `vfd` is unknown or was already closed.
+    pub fn get(&self, vfd: i32) -> Result<&PclFileHandle, ErrorCode> {
+        self.handles.get(&vfd).ok_or(ErrorCode::EpersInvalidHandle)
+    }
+
+    /// Duplicates an existing virtual descriptor: a new id is allocated that shares the same
+    /// backing PCL fd and metadata, so closing one does not close the other until both are
+    /// closed.
+    ///
+    /// Return:
+    ///   * `Ok(new_vfd)` with the newly allocated descriptor.
+    ///   * `Err(ErrorCode::EpersInvalidHandle)` if `vfd` is unknown or was already closed.
+    pub fn dup(&mut self, vfd: i32) -> Result<i32, ErrorCode> {
+        let handle = self.get(vfd)?.clone();
+        let new_vfd = self.allocate_vfd();
+        self.handles.insert(new_vfd, handle);
+        Ok(new_vfd)
+    }
+
+    /// Closes a virtual descriptor.
+    ///
+    /// The backing PCL fd is only actually closed through [`close_file`] once no other virtual
+    /// descriptor (from a prior [`dup`](Self::dup)) still references it.
+    ///
+    /// Return:
+    ///   * `Ok(())` if `vfd` was open and is now closed.
+    ///   * `Err(ErrorCode::EpersInvalidHandle)` if `vfd` is unknown or was already closed, rather
+    ///     than silently double-closing the backing fd.
+    pub fn close(&mut self, vfd: i32) -> Result<(), ErrorCode> {
+        let handle = self
+            .handles
+            .remove(&vfd)
+            .ok_or(ErrorCode::EpersInvalidHandle)?;
+
+        if !self.handles.values().any(|h| h.fd == handle.fd) {
+            close_file(handle.fd)?;
         }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `deinit_library()` call with no matching `init_library()` must not wrap
+    /// `INIT_REFCOUNT` past zero -- that would permanently wedge every later
+    /// `init_library()`/`deinit_library()` onto the reference-counted fast path and never touch
+    /// the real library again.
+    #[test]
+    fn deinit_without_matching_init_does_not_wrap_refcount() {
+        assert_eq!(INIT_REFCOUNT.load(Ordering::Acquire), 0);
+
+        assert_eq!(deinit_library(), Ok(0));
+
+        assert_eq!(INIT_REFCOUNT.load(Ordering::Acquire), 0);
+    }
+
+    /// Many threads racing [`claim_init_refcount`] while `INIT_REFCOUNT` is 0 must produce
+    /// exactly one [`InitClaim::Transition`] -- the atomic CAS that backs it is what stops two
+    /// callers from both observing a 0 refcount and both invoking the real `pclInitLibrary`.
+    #[test]
+    fn concurrent_init_claims_exactly_one_transition() {
+        assert_eq!(INIT_REFCOUNT.load(Ordering::Acquire), 0);
+
+        const THREADS: usize = 16;
+        let transitions = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    if matches!(claim_init_refcount(), InitClaim::Transition) {
+                        transitions.fetch_add(1, Ordering::AcqRel);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(transitions.load(Ordering::Acquire), 1);
+        assert_eq!(INIT_REFCOUNT.load(Ordering::Acquire), THREADS);
+
+        // Restore shared state so this doesn't affect other tests in this binary.
+        INIT_REFCOUNT.store(0, Ordering::Release);
     }
 }
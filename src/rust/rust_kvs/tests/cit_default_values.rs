@@ -361,9 +361,72 @@ fn cit_persistency_reset_all_default_values() -> Result<(), ErrorCode> {
 }
 
 #[test]
-#[ignore]
 fn cit_persistency_reset_single_default_value() -> Result<(), ErrorCode> {
-    // TODO: This test is not implemented yet.
-    // API supports resettinng only all keys.
+    // Temp directory.
+    let dir = tempdir()?;
+    let dir_path = dir.path();
+
+    // Values.
+    let keyname1 = "test_number1".to_string();
+    let keyname2 = "test_number2".to_string();
+    let default_value: f64 = 111.1;
+    let non_default_value = 333.3;
+
+    // Create defaults file for instance 0.
+    let default_id = InstanceId::new(0);
+    write_defaults_file(
+        &dir_path.to_string_lossy().to_string(),
+        HashMap::from([
+            (keyname1.clone(), JsonValue::from(default_value)),
+            (keyname2.clone(), JsonValue::from(default_value)),
+        ]),
+        default_id.clone(),
+    )?;
+
+    // Assertions.
+    {
+        // KVS instance with defaults.
+        let kvs_with_defaults = Kvs::open(
+            default_id.clone(),
+            dir_path.to_path_buf(),
+            OpenNeedDefaults::Required,
+            OpenNeedKvs::Optional,
+        )?;
+
+        // Set both keys to a non-default value.
+        kvs_with_defaults.set_value(&keyname1, non_default_value)?;
+        kvs_with_defaults.set_value(&keyname2, non_default_value)?;
+        assert!(
+            !kvs_with_defaults.is_value_default(&keyname1)?,
+            "kvs_with_defaults: key '{}' should NOT be default after set",
+            keyname1
+        );
+        assert!(
+            !kvs_with_defaults.is_value_default(&keyname2)?,
+            "kvs_with_defaults: key '{}' should NOT be default after set",
+            keyname2
+        );
+
+        // Remove only keyname1 - it should revert to default while keyname2 is untouched.
+        kvs_with_defaults.remove_key(&keyname1)?;
+        assert!(
+            kvs_with_defaults.is_value_default(&keyname1)?,
+            "kvs_with_defaults: key '{}' should be default after remove",
+            keyname1
+        );
+        assert!(
+            !kvs_with_defaults.is_value_default(&keyname2)?,
+            "kvs_with_defaults: key '{}' should still NOT be default",
+            keyname2
+        );
+        assert_eq!(
+            kvs_with_defaults.get_value::<f64>(&keyname2)?,
+            non_default_value,
+            "kvs_with_defaults: key '{}' should keep its non-default value {}",
+            keyname2,
+            non_default_value
+        );
+    }
+
     Ok(())
 }